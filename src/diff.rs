@@ -0,0 +1,350 @@
+//! Differences between two versions of an expression, e.g. before and after a step.
+//!
+//! The nodes of both versions are matched by their `id`, which Poincare preserves across the
+//! steps of a simplification.
+
+use std::collections::HashMap;
+
+use colored::*;
+
+use crate::poincare::{Decorator, PoincareNode};
+use crate::theme::theme;
+
+/// How a node of the new version of an expression differs from the old version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// the node isn't in the old version, nor its parent
+    Inserted,
+    /// the node has another parent, or another place among its siblings
+    Moved,
+    /// the node has another name or other attributes, e.g. the numerator of a `Rational`
+    ValueChanged,
+}
+
+/// Where a node is in a version of an expression
+struct Position<'a> {
+    node: &'a PoincareNode,
+    parent: Option<&'a PoincareNode>,
+}
+
+fn index_nodes<'a>(
+    node: &'a PoincareNode,
+    parent: Option<&'a PoincareNode>,
+    index: &mut HashMap<&'a str, Position<'a>>,
+) {
+    index.insert(&node.id, Position { node, parent });
+    for child in &node.children {
+        index_nodes(child, Some(node), index);
+    }
+}
+
+/// The differences between two versions of an expression
+pub struct TreeDiff<'a> {
+    old: HashMap<&'a str, Position<'a>>,
+    changes: HashMap<&'a str, Change>,
+    /// the old version, if it has been replaced as a whole
+    replaced: Option<&'a PoincareNode>,
+    /// removed nodes, by the id of their parent, after which they're displayed
+    removed: HashMap<&'a str, Vec<&'a PoincareNode>>,
+}
+impl<'a> TreeDiff<'a> {
+    pub fn new(old: &'a PoincareNode, new: &'a PoincareNode) -> Self {
+        let mut old_index = HashMap::new();
+        index_nodes(old, None, &mut old_index);
+        let mut new_index = HashMap::new();
+        index_nodes(new, None, &mut new_index);
+        let mut diff = Self {
+            old: old_index,
+            changes: HashMap::new(),
+            replaced: None,
+            removed: HashMap::new(),
+        };
+        diff.find_changes(new, None, &new_index);
+        diff.find_removed(old, &new_index);
+        diff
+    }
+    fn find_changes(
+        &mut self,
+        node: &'a PoincareNode,
+        parent: Option<&'a PoincareNode>,
+        new_index: &HashMap<&'a str, Position<'a>>,
+    ) {
+        let parent_id = parent.map(|parent| parent.id.as_str());
+        match self.old.get(node.id.as_str()) {
+            // the parent is already highlighted as a whole
+            None if parent.is_some_and(|parent| !self.old.contains_key(parent.id.as_str())) => (),
+            None => {
+                self.changes.insert(&node.id, Change::Inserted);
+            }
+            Some(old) if old.node.name != node.name || old.node.attributes != node.attributes => {
+                self.changes.insert(&node.id, Change::ValueChanged);
+            }
+            Some(old) if old.parent.map(|parent| parent.id.as_str()) != parent_id => {
+                self.changes.insert(&node.id, Change::Moved);
+            }
+            Some(_) => (),
+        }
+        if let Some(old) = self.old.get(node.id.as_str()) {
+            // the children kept under the same parent must be in the same order
+            let kept = |children: &'a [PoincareNode], index: &HashMap<&'a str, Position<'a>>| {
+                children
+                    .iter()
+                    .filter(|child| {
+                        index
+                            .get(child.id.as_str())
+                            .and_then(|position| position.parent)
+                            .is_some_and(|parent| parent.id == node.id)
+                    })
+                    .map(|child| child.id.as_str())
+                    .collect::<Vec<&str>>()
+            };
+            let old_order = kept(&old.node.children, new_index);
+            let new_order = kept(&node.children, &self.old);
+            for (old_id, new_id) in old_order.iter().zip(&new_order) {
+                if old_id != new_id {
+                    self.changes.entry(new_id).or_insert(Change::Moved);
+                }
+            }
+        }
+        for child in &node.children {
+            self.find_changes(child, Some(node), new_index);
+        }
+    }
+    /// attaches the removed nodes to their parent, which is kept in the new version
+    ///
+    /// They aren't attached to a kept sibling, which could be put in parentheses with them.
+    fn find_removed(&mut self, old: &'a PoincareNode, new_index: &HashMap<&'a str, Position<'a>>) {
+        if !new_index.contains_key(old.id.as_str()) {
+            // the whole expression has been replaced
+            self.replaced = Some(old);
+            return;
+        }
+        let mut stack = vec![old];
+        while let Some(node) = stack.pop() {
+            for child in &node.children {
+                if new_index.contains_key(child.id.as_str()) {
+                    stack.push(child);
+                } else {
+                    self.removed.entry(&node.id).or_default().push(child);
+                }
+            }
+        }
+    }
+    /// how the node with the id `id` in the new version differs from the old version
+    pub fn change(&self, id: &str) -> Option<Change> {
+        self.changes.get(id).copied()
+    }
+    /// the removed nodes, without the nodes inside them
+    pub fn removed(&self) -> impl Iterator<Item = &'a PoincareNode> + '_ {
+        self.replaced
+            .into_iter()
+            .chain(self.removed.values().flatten().copied())
+    }
+    /// the subtrees of both versions which changed: the inserted, moved, value-changed and removed
+    /// nodes, and the old version of the value-changed nodes, `new` being the new version
+    pub fn changed_subtrees(&self, new: &'a PoincareNode) -> Vec<&'a PoincareNode> {
+        let mut subtrees: Vec<&'a PoincareNode> = Vec::new();
+        let mut stack = vec![new];
+        while let Some(node) = stack.pop() {
+            match self.change(&node.id) {
+                Some(change) => {
+                    subtrees.push(node);
+                    if change == Change::ValueChanged {
+                        subtrees.extend(self.old.get(node.id.as_str()).map(|old| old.node));
+                    }
+                }
+                None => stack.extend(&node.children),
+            }
+        }
+        subtrees.extend(self.removed());
+        subtrees
+    }
+    /// true if both versions are the same
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.replaced.is_none() && self.removed.is_empty()
+    }
+    /// displays the new version of the expression with the differences highlighted:
+    /// `{+inserted+}`, `[-removed-]`, `{>moved<}` and `{~changed~}`, or `{~old => new~}` for nodes
+    /// without children
+    ///
+    /// The removed nodes are displayed after the node they were removed from, e.g. `a ^ b [-c-]`
+    /// when `c` was a child of the `Power`, and a replaced expression before the new one.
+    pub fn pretty_print(&self, new: &PoincareNode, long_form: bool) -> ColoredString {
+        self.decorated_print(new, long_form, &|_, _, output| output)
+    }
+    /// like [`TreeDiff::pretty_print`], but the output of each node of the new version goes through
+    /// `decorate` before the differences are highlighted
+    pub fn decorated_print(
+        &self,
+        new: &PoincareNode,
+        long_form: bool,
+        decorate: &Decorator<'_>,
+    ) -> ColoredString {
+        let theme = theme();
+        new.decorated_print(0, long_form, &|node, nesting_level, output| {
+            let output = decorate(node, nesting_level, output);
+            let removed = |nodes: &[&PoincareNode]| {
+                let removed_outputs: Vec<String> = nodes
+                    .iter()
+                    .map(|removed| {
+                        format!(
+                            "{}{}{}",
+                            "[-".color(theme.removed).strikethrough(),
+                            removed.pretty_print(nesting_level, long_form),
+                            "-]".color(theme.removed).strikethrough()
+                        )
+                    })
+                    .collect();
+                removed_outputs.join(" ")
+            };
+            let output = match self.change(&node.id) {
+                None => output.to_string(),
+                Some(Change::Inserted) => {
+                    format!(
+                        "{}{}{}",
+                        "{+".color(theme.inserted).bold(),
+                        output,
+                        "+}".color(theme.inserted).bold()
+                    )
+                }
+                Some(Change::Moved) => {
+                    format!(
+                        "{}{}{}",
+                        "{>".color(theme.moved).bold(),
+                        output,
+                        "<}".color(theme.moved).bold()
+                    )
+                }
+                Some(Change::ValueChanged) => match self.old.get(node.id.as_str()) {
+                    Some(old) if old.node.children.is_empty() && node.children.is_empty() => {
+                        format!(
+                            "{}{}{}{}{}",
+                            "{~".color(theme.changed).bold(),
+                            old.node.pretty_print(nesting_level, long_form),
+                            " => ".color(theme.changed).bold(),
+                            output,
+                            "~}".color(theme.changed).bold()
+                        )
+                    }
+                    _ => format!(
+                        "{}{}{}",
+                        "{~".color(theme.changed).bold(),
+                        output,
+                        "~}".color(theme.changed).bold()
+                    ),
+                },
+            };
+            let replaced = match self.replaced {
+                Some(replaced) if std::ptr::eq(node, new) => removed(&[replaced]),
+                _ => String::new(),
+            };
+            let children = removed(
+                self.removed
+                    .get(node.id.as_str())
+                    .map_or(&[], |nodes| nodes.as_slice()),
+            );
+            let separator = |text: &str| if text.is_empty() { "" } else { " " };
+            format!(
+                "{}{}{}{}{}",
+                replaced,
+                separator(&replaced),
+                output,
+                separator(&children),
+                children
+            )
+            .normal()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+    use crate::poincare::strip_colors;
+    use crate::reduction::StepNode;
+
+    fn step(before: &str, after: &str) -> StepNode {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State><State name="after">{}</State></Step>"#,
+            before, after
+        );
+        parse_log(&log).unwrap().remove(0)
+    }
+
+    fn diff_print(step: &StepNode) -> String {
+        let (before, after) = (step.before.as_ref().unwrap(), step.after.as_ref().unwrap());
+        strip_colors(
+            &TreeDiff::new(before, after)
+                .pretty_print(after, false)
+                .to_string(),
+        )
+    }
+
+    const A_PLUS_B: &str = r#"<Addition id="2"><Symbol id="3" name="a"></Symbol><Symbol id="4" name="b"></Symbol></Addition>"#;
+
+    #[test]
+    fn finds_the_changes() {
+        let step = step(
+            r#"<Division id="1"><Symbol id="2" name="a"></Symbol><Rational id="3" negative="0" numerator="1" denominator="2"></Rational></Division>"#,
+            r#"<Division id="1"><Rational id="3" negative="0" numerator="3" denominator="2"></Rational><Symbol id="2" name="a"></Symbol><Symbol id="4" name="b"></Symbol></Division>"#,
+        );
+        let diff = TreeDiff::new(step.before.as_ref().unwrap(), step.after.as_ref().unwrap());
+        assert_eq!(diff.change("1"), None);
+        assert_eq!(diff.change("2"), Some(Change::Moved));
+        assert_eq!(diff.change("3"), Some(Change::ValueChanged));
+        assert_eq!(diff.change("4"), Some(Change::Inserted));
+        assert_eq!(diff.removed().count(), 0);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn finds_no_change_in_the_same_expression() {
+        let step = step(A_PLUS_B, A_PLUS_B);
+        let diff = TreeDiff::new(step.before.as_ref().unwrap(), step.after.as_ref().unwrap());
+        assert!(diff.is_empty());
+        assert_eq!(diff_print(&step), "a + b");
+    }
+
+    #[test]
+    fn displays_removed_nodes_after_their_parent() {
+        let step = step(
+            &format!(
+                r#"<Power id="1">{}<Symbol id="5" name="c"></Symbol></Power>"#,
+                A_PLUS_B
+            ),
+            &format!(
+                r#"<Power id="1">{}<Rational id="6" negative="0" numerator="1" denominator="2"></Rational></Power>"#,
+                A_PLUS_B
+            ),
+        );
+        assert_eq!(diff_print(&step), "(a + b) ^ ({+1/2+}) [-c-]");
+    }
+
+    #[test]
+    fn displays_removed_nodes_inside_the_parentheses_of_their_parent() {
+        let step = step(
+            &format!(
+                r#"<Multiplication id="1">{}<Symbol id="5" name="c"></Symbol></Multiplication>"#,
+                A_PLUS_B.replace(
+                    "</Addition>",
+                    r#"<Symbol id="6" name="d"></Symbol></Addition>"#
+                )
+            ),
+            &format!(
+                r#"<Multiplication id="1">{}<Symbol id="5" name="c"></Symbol></Multiplication>"#,
+                A_PLUS_B
+            ),
+        );
+        assert_eq!(diff_print(&step), "(a + b [-d-]) * c");
+    }
+
+    #[test]
+    fn displays_a_replaced_expression_before_the_new_one() {
+        let step = step(
+            r#"<Symbol id="1" name="y"></Symbol>"#,
+            r#"<Symbol id="2" name="y"></Symbol>"#,
+        );
+        assert_eq!(diff_print(&step), "[-y-] {+y+}");
+    }
+}
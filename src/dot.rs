@@ -0,0 +1,343 @@
+//! Graphviz DOT export of the steps, e.g. to be drawn with `dot -Tsvg -O`.
+//!
+//! Either each step is a graph with the trees of the expressions before and after it side by side,
+//! or each top-level step is a graph of the steps it calls.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::output::Output;
+use crate::poincare::{strip_colors, PoincareNode};
+use crate::reduction::{StepNode, StepPart};
+
+/// fill color of the nodes of the expression after a step which were already before it
+const SURVIVED_COLOR: &str = "white";
+/// fill color of the nodes whose name or attributes changed during the step
+const CHANGED_COLOR: &str = "gold";
+/// fill color of the nodes which weren't in the expression before the step
+const NEW_COLOR: &str = "palegreen";
+/// fill color of the nodes of the expression before a step which aren't after it
+const REMOVED_COLOR: &str = "lightpink";
+/// fill color of the steps in which the log ends
+const TRUNCATED_COLOR: &str = "orange";
+
+/// What is drawn in the graphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotGraph {
+    /// a graph per step, with the expressions before and after it
+    Expressions,
+    /// a graph per top-level step, with the steps it calls
+    Steps,
+}
+
+/// Writes the steps as DOT graphs, one after the other
+pub struct DotOutput<W: Write> {
+    writer: W,
+    graph: DotGraph,
+    /// number of graphs written, used to name them
+    graphs: usize,
+}
+impl<W: Write> DotOutput<W> {
+    pub fn new(writer: W, graph: DotGraph) -> Self {
+        Self {
+            writer,
+            graph,
+            graphs: 0,
+        }
+    }
+    /// writes the graph of the expressions of `step`, then those of its substeps
+    fn write_expressions(&mut self, step: &StepNode) -> io::Result<()> {
+        if step.before.is_some() || step.after.is_some() {
+            self.graphs += 1;
+            writeln!(self.writer, "digraph step_{} {{", self.graphs)?;
+            writeln!(self.writer, "  label={};", quote(&step.name))?;
+            writeln!(self.writer, "  labelloc=t;")?;
+            writeln!(self.writer, "  node [shape=box, style=filled];")?;
+            let mut ids = HashMap::new();
+            if let Some(before) = &step.before {
+                index_ids(before, &mut ids);
+            }
+            let mut after_ids = HashMap::new();
+            if let Some(after) = &step.after {
+                index_ids(after, &mut after_ids);
+            }
+            let mut count = 0;
+            if let Some(before) = &step.before {
+                writeln!(self.writer, "  subgraph cluster_before {{")?;
+                writeln!(self.writer, "    label=\"before\";")?;
+                self.write_tree(before, "b", &mut count, &|node| {
+                    if after_ids.contains_key(node.id.as_str()) {
+                        SURVIVED_COLOR
+                    } else {
+                        REMOVED_COLOR
+                    }
+                })?;
+                writeln!(self.writer, "  }}")?;
+            }
+            if let Some(after) = &step.after {
+                writeln!(self.writer, "  subgraph cluster_after {{")?;
+                writeln!(self.writer, "    label=\"after\";")?;
+                self.write_tree(
+                    after,
+                    "a",
+                    &mut count,
+                    &|node| match ids.get(node.id.as_str()) {
+                        None => NEW_COLOR,
+                        Some(old) if old.name != node.name || old.attributes != node.attributes => {
+                            CHANGED_COLOR
+                        }
+                        Some(_) => SURVIVED_COLOR,
+                    },
+                )?;
+                writeln!(self.writer, "  }}")?;
+            }
+            writeln!(self.writer, "}}")?;
+        }
+        for part in &step.parts {
+            if let StepPart::Substep(substep) = part {
+                self.write_expressions(substep)?;
+            }
+        }
+        Ok(())
+    }
+    /// writes the nodes of the tree `node` and the edges to their children, returning the name
+    /// of the DOT node of `node`
+    ///
+    /// The DOT nodes are named with `prefix` and a number, as several nodes can have the same id.
+    fn write_tree(
+        &mut self,
+        node: &PoincareNode,
+        prefix: &str,
+        count: &mut usize,
+        color: &dyn Fn(&PoincareNode) -> &'static str,
+    ) -> io::Result<String> {
+        let name = format!("{}{}", prefix, count);
+        *count += 1;
+        let mut label = node.name.clone();
+        if let Some(attributes) = &node.attributes {
+            label.push('\n');
+            label.push_str(&attributes.pretty_print());
+        }
+        writeln!(
+            self.writer,
+            "    {} [label={}, tooltip={}, fillcolor={}];",
+            name,
+            quote(&label),
+            quote(&format!("id {}", node.id)),
+            color(node)
+        )?;
+        for child in &node.children {
+            let child_name = self.write_tree(child, prefix, count, color)?;
+            writeln!(self.writer, "    {} -> {};", name, child_name)?;
+        }
+        Ok(name)
+    }
+    /// writes the graph of the steps called by `step`
+    fn write_steps(&mut self, step: &StepNode) -> io::Result<()> {
+        self.graphs += 1;
+        writeln!(self.writer, "digraph steps_{} {{", self.graphs)?;
+        writeln!(
+            self.writer,
+            "  node [shape=box, style=filled, fillcolor=white];"
+        )?;
+        self.write_step_node(step, &mut 0)?;
+        writeln!(self.writer, "}}")
+    }
+    /// writes the node of `step` and of its substeps, returning the name of the DOT node of `step`
+    fn write_step_node(&mut self, step: &StepNode, count: &mut usize) -> io::Result<String> {
+        let name = format!("s{}", count);
+        *count += 1;
+        let short_form = |node: &Option<PoincareNode>| match node {
+            Some(node) => strip_colors(&node.pretty_print(0, false).to_string()),
+            None => String::from("?"),
+        };
+        let tooltip = format!(
+            "{} -> {}",
+            short_form(&step.before),
+            short_form(&step.after)
+        );
+        writeln!(
+            self.writer,
+            "  {} [label={}, tooltip={}{}];",
+            name,
+            quote(&step.name),
+            quote(&tooltip),
+            if step.incomplete {
+                format!(", fillcolor={}", TRUNCATED_COLOR)
+            } else {
+                String::new()
+            }
+        )?;
+        for part in &step.parts {
+            if let StepPart::Substep(substep) = part {
+                let substep_name = self.write_step_node(substep, count)?;
+                writeln!(self.writer, "  {} -> {};", name, substep_name)?;
+            }
+        }
+        Ok(name)
+    }
+}
+impl<W: Write> Output for DotOutput<W> {
+    fn write_step(&mut self, step: &StepNode) -> io::Result<()> {
+        match self.graph {
+            DotGraph::Expressions => self.write_expressions(step),
+            DotGraph::Steps => self.write_steps(step),
+        }
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn index_ids<'a>(node: &'a PoincareNode, ids: &mut HashMap<&'a str, &'a PoincareNode>) {
+    ids.insert(&node.id, node);
+    for child in &node.children {
+        index_ids(child, ids);
+    }
+}
+
+/// `text` as a quoted DOT string, where new lines are kept
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    /// `x + 1/2 + z` becoming `x + 3/4 + y`: `x` survives, `1/2` changes, `y` is new and `z` is
+    /// removed
+    const LOG: &str = r#"<Step name="simplify">
+  <State name="before"><Addition id="1"><Symbol id="2" name="x"></Symbol><Rational id="3" negative="0" numerator="1" denominator="2"></Rational><Symbol id="5" name="z"></Symbol></Addition></State>
+  <Step name="shallowReduce">
+    <State name="before"><Symbol id="5" name="z"></Symbol></State>
+    <State name="after"><Symbol id="4" name="y"></Symbol></State>
+  </Step>
+  <State name="after"><Addition id="1"><Symbol id="2" name="x"></Symbol><Rational id="3" negative="0" numerator="3" denominator="4"></Rational><Symbol id="4" name="y"></Symbol></Addition></State>
+</Step>"#;
+
+    fn export(log: &str, graph: DotGraph) -> String {
+        let mut buffer = Vec::new();
+        let mut output = DotOutput::new(&mut buffer, graph);
+        for step in parse_log(log).unwrap() {
+            output.write_step(&step).unwrap();
+        }
+        output.finish().unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn colors_the_nodes_by_id() {
+        let graphs = export(LOG, DotGraph::Expressions);
+        assert!(graphs.starts_with(
+            r#"digraph step_1 {
+  label="simplify";
+  labelloc=t;
+  node [shape=box, style=filled];
+  subgraph cluster_before {
+    label="before";
+    b0 [label="Addition", tooltip="id 1", fillcolor=white];
+    b1 [label="Symbol\nx", tooltip="id 2", fillcolor=white];
+    b0 -> b1;
+    b2 [label="Rational\n1/2", tooltip="id 3", fillcolor=white];
+    b0 -> b2;
+    b3 [label="Symbol\nz", tooltip="id 5", fillcolor=lightpink];
+    b0 -> b3;
+  }
+  subgraph cluster_after {
+    label="after";
+    a4 [label="Addition", tooltip="id 1", fillcolor=white];
+    a5 [label="Symbol\nx", tooltip="id 2", fillcolor=white];
+    a4 -> a5;
+    a6 [label="Rational\n3/4", tooltip="id 3", fillcolor=gold];
+    a4 -> a6;
+    a7 [label="Symbol\ny", tooltip="id 4", fillcolor=palegreen];
+    a4 -> a7;
+  }
+}
+"#
+        ));
+    }
+
+    #[test]
+    fn writes_a_graph_per_step_with_expressions() {
+        let graphs = export(LOG, DotGraph::Expressions);
+        assert!(graphs.ends_with(
+            r#"}
+digraph step_2 {
+  label="shallowReduce";
+  labelloc=t;
+  node [shape=box, style=filled];
+  subgraph cluster_before {
+    label="before";
+    b0 [label="Symbol\nz", tooltip="id 5", fillcolor=lightpink];
+  }
+  subgraph cluster_after {
+    label="after";
+    a1 [label="Symbol\ny", tooltip="id 4", fillcolor=palegreen];
+  }
+}
+"#
+        ));
+        // the clusters of a step with a single expression
+        let log = r#"<Step name="reduce"><State name="before"><Symbol id="1" name="x"></Symbol></State></Step>"#;
+        assert_eq!(
+            export(log, DotGraph::Expressions),
+            r#"digraph step_1 {
+  label="reduce";
+  labelloc=t;
+  node [shape=box, style=filled];
+  subgraph cluster_before {
+    label="before";
+    b0 [label="Symbol\nx", tooltip="id 1", fillcolor=lightpink];
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn writes_the_graph_of_the_steps() {
+        assert_eq!(
+            export(LOG, DotGraph::Steps),
+            r#"digraph steps_1 {
+  node [shape=box, style=filled, fillcolor=white];
+  s0 [label="simplify", tooltip="x + 1/2 + z -> x + 3/4 + y"];
+  s1 [label="shallowReduce", tooltip="z -> y"];
+  s0 -> s1;
+}
+"#
+        );
+        let truncated = r#"<Step name="simplify"><State name="before"><Symbol id="1" name="x"></Symbol></State>"#;
+        assert_eq!(
+            export(truncated, DotGraph::Steps),
+            r#"digraph steps_1 {
+  node [shape=box, style=filled, fillcolor=white];
+  s0 [label="simplify", tooltip="x -> ?", fillcolor=orange];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn escapes_the_labels() {
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"a\b"), r#""a\\b""#);
+        assert_eq!(quote("a\nb"), r#""a\nb""#);
+        let log = r#"<Step name="a &quot;b&quot; \ c"><State name="before"><Symbol id="1" name="\"></Symbol></State></Step>"#;
+        let graphs = export(log, DotGraph::Expressions);
+        assert!(graphs.contains(r#"  label="a \"b\" \\ c";"#));
+        assert!(graphs.contains(r#"[label="Symbol\n\\", "#));
+    }
+}
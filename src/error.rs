@@ -0,0 +1,137 @@
+use std::fmt::{self, Display};
+
+use quick_xml::events::Event;
+
+/// A position in a log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Location {
+    /// offset in bytes from the start of the log
+    pub offset: usize,
+    /// line, starting at 1
+    pub line: usize,
+    /// column in bytes, starting at 1
+    pub column: usize,
+}
+
+/// An error encountered while parsing a log
+#[derive(Debug)]
+pub struct ParseError {
+    /// where the error was detected
+    pub location: Location,
+    /// elements enclosing the error, from the outermost one, e.g. `["Step[name=...]", "State"]`
+    pub path: Vec<String>,
+    pub kind: ParseErrorKind,
+    /// number of elements left open in the reader by the error, used to skip the broken step
+    depth: isize,
+}
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// the log isn't well-formed XML
+    Xml(quick_xml::Error),
+    /// the log is valid XML but doesn't follow the expected structure
+    UnexpectedEvent { expected: String, found: String },
+    /// an element lacks an attribute it must have
+    MissingAttribute { element: String, attribute: String },
+}
+
+impl ParseError {
+    fn new(location: Location, kind: ParseErrorKind, depth: isize) -> Self {
+        Self {
+            location,
+            path: Vec::new(),
+            kind,
+            depth,
+        }
+    }
+    pub(crate) fn xml(location: Location, error: quick_xml::Error) -> Self {
+        Self::new(location, ParseErrorKind::Xml(error), 0)
+    }
+    pub(crate) fn unexpected(location: Location, expected: &str, found: &Event) -> Self {
+        // the unexpected event may have opened or closed an element
+        let depth = match found {
+            Event::Start(_) => 1,
+            Event::End(_) => -1,
+            _ => 0,
+        };
+        Self::new(
+            location,
+            ParseErrorKind::UnexpectedEvent {
+                expected: String::from(expected),
+                found: describe_event(found),
+            },
+            depth,
+        )
+    }
+    /// `element` has been opened but it can't be used
+    pub(crate) fn missing_attribute(location: Location, element: &str, attribute: &str) -> Self {
+        Self::new(
+            location,
+            ParseErrorKind::MissingAttribute {
+                element: String::from(element),
+                attribute: String::from(attribute),
+            },
+            1,
+        )
+    }
+    /// records that the error happened inside the element described by `element`
+    pub(crate) fn within(mut self, element: String) -> Self {
+        self.path.insert(0, element);
+        self.depth += 1;
+        self
+    }
+    /// number of elements which must be closed to get back to the top-level of the log
+    pub(crate) fn depth(&self) -> usize {
+        self.depth.max(0) as usize
+    }
+}
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (byte {})",
+            self.location.line, self.location.column, self.location.offset
+        )?;
+        if !self.path.is_empty() {
+            write!(f, ", in `{}`", self.path.join(" > "))?;
+        }
+        match &self.kind {
+            ParseErrorKind::Xml(error) => write!(f, ": {}", error),
+            ParseErrorKind::UnexpectedEvent { expected, found } => {
+                write!(f, ": expected {}, found {}", expected, found)
+            }
+            ParseErrorKind::MissingAttribute { element, attribute } => {
+                write!(f, ": `{}` has no `{}` attribute", element, attribute)
+            }
+        }
+    }
+}
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Xml(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+fn describe_event(event: &Event) -> String {
+    match event {
+        Event::Start(start) => format!(
+            "start of `{}`",
+            String::from_utf8_lossy(start.name().as_ref())
+        ),
+        Event::End(end) => format!("end of `{}`", String::from_utf8_lossy(end.name().as_ref())),
+        Event::Empty(empty) => format!(
+            "empty element `{}`",
+            String::from_utf8_lossy(empty.name().as_ref())
+        ),
+        Event::Text(text) => format!("text `{}`", String::from_utf8_lossy(text)),
+        Event::CData(_) => String::from("CDATA section"),
+        Event::Comment(_) => String::from("comment"),
+        Event::Decl(_) => String::from("XML declaration"),
+        Event::PI(_) => String::from("processing instruction"),
+        Event::DocType(_) => String::from("DOCTYPE"),
+        Event::Eof => String::from("end of file"),
+    }
+}
@@ -0,0 +1,352 @@
+//! Selection of the steps to display by their name or by the nodes they touch.
+
+use regex::Regex;
+
+use crate::diff::TreeDiff;
+use crate::pattern::Pattern;
+use crate::poincare::PoincareNode;
+use crate::reduction::{FilterAction, StepNode, StepPart};
+
+/// A pattern matching whole step names: either a glob, where `*` matches any text and `?` any
+/// character, e.g. `*Reduce`, or a regular expression between slashes, e.g.
+/// `/^(deep|shallow)Beautify$/`
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    regex: Regex,
+}
+impl NamePattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let regex = match pattern
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+        {
+            Some(regex) => String::from(regex),
+            None => {
+                let mut regex = String::from("^");
+                for c in pattern.chars() {
+                    match c {
+                        '*' => regex.push_str(".*"),
+                        '?' => regex.push('.'),
+                        c => regex.push_str(&regex::escape(&c.to_string())),
+                    }
+                }
+                regex.push('$');
+                regex
+            }
+        };
+        Ok(Self {
+            regex: Regex::new(&regex).map_err(|e| e.to_string())?,
+        })
+    }
+    pub fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+/// Keeps only the steps whose name matches one of the `only` patterns, if there are any, and
+/// hides the steps whose name matches one of the `hide` patterns
+///
+/// The hidden steps are dropped with their substeps, or collapsed so that their substeps and
+/// states take their place. The steps which aren't selected by `only` patterns but contain
+/// selected steps are kept, or collapsed, to show where the selected steps are.
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    only: Vec<NamePattern>,
+    hide: Vec<NamePattern>,
+    collapse: bool,
+}
+impl NameFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// selects the steps whose name matches `pattern`
+    pub fn only(mut self, pattern: NamePattern) -> Self {
+        self.only.push(pattern);
+        self
+    }
+    /// hides the steps whose name matches `pattern`
+    pub fn hide(mut self, pattern: NamePattern) -> Self {
+        self.hide.push(pattern);
+        self
+    }
+    /// collapse the filtered steps instead of dropping them
+    pub fn collapse(mut self, collapse: bool) -> Self {
+        self.collapse = collapse;
+        self
+    }
+    /// true if the filter keeps all the steps
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.hide.is_empty()
+    }
+    /// what to do with `step`, whose substeps are filtered separately
+    pub fn action(&self, step: &StepNode) -> FilterAction {
+        let filtered = if self.collapse {
+            FilterAction::Collapse
+        } else {
+            FilterAction::Drop
+        };
+        if self.is_hidden(step) {
+            filtered
+        } else if self.is_selected(step) {
+            FilterAction::Keep
+        } else if self.selects_substep(step) {
+            // keep the context of the selected steps
+            if self.collapse {
+                FilterAction::Collapse
+            } else {
+                FilterAction::Keep
+            }
+        } else {
+            FilterAction::Drop
+        }
+    }
+    fn is_hidden(&self, step: &StepNode) -> bool {
+        self.hide.iter().any(|pattern| pattern.matches(&step.name))
+    }
+    fn is_selected(&self, step: &StepNode) -> bool {
+        self.only.is_empty() || self.only.iter().any(|pattern| pattern.matches(&step.name))
+    }
+    /// true if a step inside `step` is displayed
+    fn selects_substep(&self, step: &StepNode) -> bool {
+        step.parts.iter().any(|part| match part {
+            StepPart::Substep(substep) if self.is_hidden(substep) => {
+                // the substeps of a collapsed step are still displayed
+                self.collapse && self.selects_substep(substep)
+            }
+            StepPart::Substep(substep) => {
+                self.is_selected(substep) || self.selects_substep(substep)
+            }
+            StepPart::State(..) => false,
+        })
+    }
+}
+
+/// Keeps only the steps touching nodes with some names: the root of the expression before or
+/// after the step has one of the names, or one of the subtrees changed by the step contains a
+/// node with one of the names
+///
+/// The steps containing steps which are kept are also kept, or collapsed, as context.
+#[derive(Debug, Clone, Default)]
+pub struct TouchFilter {
+    names: Vec<String>,
+    collapse: bool,
+}
+impl TouchFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// keeps the steps touching nodes called `name`
+    pub fn name(mut self, name: &str) -> Self {
+        self.names.push(String::from(name));
+        self
+    }
+    /// collapse the steps kept as context instead of displaying them
+    pub fn collapse(mut self, collapse: bool) -> Self {
+        self.collapse = collapse;
+        self
+    }
+    /// true if the filter keeps all the steps
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    /// what to do with `step`, whose substeps are filtered separately
+    pub fn action(&self, step: &StepNode) -> FilterAction {
+        select_with_context(step, self.collapse, &|step| self.touches(step))
+    }
+    /// true if `step` itself touches one of the nodes
+    pub fn touches(&self, step: &StepNode) -> bool {
+        let has_name = |node: &PoincareNode| self.names.contains(&node.name);
+        if step.before.iter().chain(&step.after).any(has_name) {
+            return true;
+        }
+        fn contains<F: Fn(&PoincareNode) -> bool + Copy>(node: &PoincareNode, has_name: F) -> bool {
+            has_name(node) || node.children.iter().any(|child| contains(child, has_name))
+        }
+        match (&step.before, &step.after) {
+            (Some(before), Some(after)) => TreeDiff::new(before, after)
+                .changed_subtrees(after)
+                .into_iter()
+                .any(|subtree| contains(subtree, has_name)),
+            _ => false,
+        }
+    }
+}
+
+/// Keeps only the steps whose expressions before and after the step match patterns
+///
+/// When several patterns are given for the same expression, it must match all of them. The steps
+/// containing steps which are kept are also kept, or collapsed, as context.
+#[derive(Debug, Clone, Default)]
+pub struct MatchFilter {
+    before: Vec<Pattern>,
+    after: Vec<Pattern>,
+    collapse: bool,
+}
+impl MatchFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// keeps the steps whose expression before the step matches `pattern`, and the other
+    /// patterns given for it
+    pub fn before(mut self, pattern: Pattern) -> Self {
+        self.before.push(pattern);
+        self
+    }
+    /// keeps the steps whose expression after the step matches `pattern`, and the other patterns
+    /// given for it
+    pub fn after(mut self, pattern: Pattern) -> Self {
+        self.after.push(pattern);
+        self
+    }
+    /// collapse the steps kept as context instead of displaying them
+    pub fn collapse(mut self, collapse: bool) -> Self {
+        self.collapse = collapse;
+        self
+    }
+    /// true if the filter keeps all the steps
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
+    }
+    /// what to do with `step`, whose substeps are filtered separately
+    pub fn action(&self, step: &StepNode) -> FilterAction {
+        select_with_context(step, self.collapse, &|step| self.matches(step))
+    }
+    /// true if the expressions of `step` match the patterns
+    pub fn matches(&self, step: &StepNode) -> bool {
+        let matches = |patterns: &[Pattern], node: &Option<PoincareNode>| match node {
+            Some(node) => patterns.iter().all(|pattern| pattern.matches(node)),
+            None => patterns.is_empty(),
+        };
+        matches(&self.before, &step.before) && matches(&self.after, &step.after)
+    }
+}
+
+/// keeps `step` if it's selected, or as context if it contains a selected step
+fn select_with_context(
+    step: &StepNode,
+    collapse: bool,
+    is_selected: &dyn Fn(&StepNode) -> bool,
+) -> FilterAction {
+    fn contains_selected(step: &StepNode, is_selected: &dyn Fn(&StepNode) -> bool) -> bool {
+        step.parts.iter().any(|part| match part {
+            StepPart::Substep(substep) => {
+                is_selected(substep) || contains_selected(substep, is_selected)
+            }
+            StepPart::State(..) => false,
+        })
+    }
+    if is_selected(step) {
+        FilterAction::Keep
+    } else if contains_selected(step, is_selected) {
+        if collapse {
+            FilterAction::Collapse
+        } else {
+            FilterAction::Keep
+        }
+    } else {
+        FilterAction::Drop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    fn step(before: &str) -> StepNode {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            before
+        );
+        parse_log(&log).unwrap().remove(0)
+    }
+
+    #[test]
+    fn matches_all_the_patterns_of_an_expression() {
+        let filter = MatchFilter::new()
+            .before(Pattern::parse("..Power").unwrap())
+            .before(Pattern::parse("..Cosine").unwrap());
+        let power = r#"<Power id="2"><Symbol id="3" name="x"></Symbol><Symbol id="4" name="n"></Symbol></Power>"#;
+        let cosine = r#"<Cosine id="5"><Symbol id="6" name="x"></Symbol></Cosine>"#;
+        assert!(filter.matches(&step(&format!(
+            r#"<Addition id="1">{}{}</Addition>"#,
+            power, cosine
+        ))));
+        assert!(!filter.matches(&step(&format!(r#"<Addition id="1">{}</Addition>"#, power))));
+        assert!(!filter.matches(&step(cosine)));
+    }
+
+    #[test]
+    fn needs_the_expressions_with_patterns() {
+        let filter = MatchFilter::new().after(Pattern::parse("_").unwrap());
+        assert!(!filter.matches(&step(r#"<Symbol id="1" name="x"></Symbol>"#)));
+        assert!(MatchFilter::new().matches(&step(r#"<Symbol id="1" name="x"></Symbol>"#)));
+    }
+
+    /// a step turning `before` into `after`, with the substeps `substeps`
+    fn step_between(name: &str, before: &str, after: &str, substeps: &[String]) -> String {
+        format!(
+            r#"<Step name="{}"><State name="before">{}</State>{}<State name="after">{}</State></Step>"#,
+            name,
+            before,
+            substeps.concat(),
+            after
+        )
+    }
+
+    const X: &str = r#"<Symbol id="1" name="x"></Symbol>"#;
+    const POWER: &str = r#"<Power id="3"><Integer id="4" value="2"></Integer><Integer id="5" value="3"></Integer></Power>"#;
+
+    fn sum(term: &str) -> String {
+        format!(r#"<Addition id="2">{}{}</Addition>"#, X, term)
+    }
+
+    fn touches(names: &[&str], log: &str) -> bool {
+        let filter = names
+            .iter()
+            .fold(TouchFilter::new(), |filter, name| filter.name(name));
+        filter.touches(&parse_log(log).unwrap()[0])
+    }
+
+    #[test]
+    fn touches_the_roots_of_the_expressions() {
+        let log = step_between("s", &sum(POWER), &sum(POWER), &[]);
+        assert!(touches(&["Addition"], &log));
+        assert!(!touches(&["Power"], &log));
+    }
+
+    #[test]
+    fn touches_the_removed_and_inserted_nodes() {
+        let eight = r#"<Integer id="6" value="8"></Integer>"#;
+        let log = step_between("s", &sum(POWER), &sum(eight), &[]);
+        assert!(touches(&["Power"], &log));
+        assert!(touches(&["Integer"], &log));
+        assert!(!touches(&["Symbol"], &log));
+    }
+
+    #[test]
+    fn touches_the_old_version_of_the_changed_nodes() {
+        // the same node, turned into a multiplication in place
+        let product = POWER.replace("Power", "Multiplication");
+        let log = step_between("s", &sum(POWER), &sum(&product), &[]);
+        assert!(touches(&["Power"], &log));
+        assert!(touches(&["Multiplication"], &log));
+        assert!(!touches(&["Symbol"], &log));
+    }
+
+    #[test]
+    fn keeps_the_steps_containing_touching_steps_as_context() {
+        let eight = r#"<Integer id="6" value="8"></Integer>"#;
+        let inner = step_between("inner", &sum(POWER), &sum(eight), &[]);
+        let outer = step_between("outer", X, X, &[inner]);
+        let step = parse_log(&outer).unwrap().remove(0);
+        let filter = TouchFilter::new().name("Power");
+        assert_eq!(filter.action(&step), FilterAction::Keep);
+        assert_eq!(filter.collapse(true).action(&step), FilterAction::Collapse);
+        let unrelated = parse_log(&step_between("s", X, X, &[])).unwrap().remove(0);
+        assert_eq!(
+            TouchFilter::new().name("Power").action(&unrelated),
+            FilterAction::Drop
+        );
+    }
+}
@@ -0,0 +1,483 @@
+//! Self-contained HTML report of the steps, where each step is a collapsible `<details>` block.
+//!
+//! The expressions are displayed with [`PoincareNode::decorated_print`], and the ANSI colors of
+//! the terminal are turned into CSS classes, so that the nesting levels keep their colors. Each
+//! node is a `<span>` whose title is its id, name and attributes, shown when hovering it, and the
+//! long form of each expression is the title of its label.
+
+use std::io::{self, Write};
+
+use colored::*;
+
+use crate::diff::TreeDiff;
+use crate::output::Output;
+use crate::poincare::{strip_colors, PoincareNode};
+use crate::reduction::{StepNode, StepPart, ViewOptions};
+use crate::theme::ColorOverride;
+
+/// starts the title of a node in the text of an expression, the node starting after
+/// [`NODE_TITLE_END`]
+const NODE_START: char = '\u{E000}';
+const NODE_TITLE_END: char = '\u{E001}';
+const NODE_END: char = '\u{E002}';
+
+const HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Poincare steps</title>
+<style>
+body { background: #1e1e1e; color: #d4d4d4; font-family: monospace; font-size: 14px; }
+details { margin-left: 1.5em; border-left: 1px solid #3a6f7a; padding-left: 0.5em; }
+body > details { margin-left: 0; margin-bottom: 1em; }
+summary { color: #29b8db; font-weight: bold; cursor: pointer; }
+.count { font-weight: normal; }
+.state { white-space: pre-wrap; margin: 0.1em 0; }
+.label { color: #29b8db; font-weight: bold; }
+.matrix { margin: 0 0 0 2em; }
+.truncated { color: #f14c4c; font-weight: bold; }
+.node:hover { background: #333; }
+.black { color: #000000; } .bright-black { color: #666666; }
+.red { color: #cd3131; } .green { color: #0dbc79; } .yellow { color: #e5e510; }
+.blue { color: #2472c8; } .magenta { color: #bc3fbc; } .cyan { color: #11a8cd; }
+.white { color: #e5e5e5; } .bright-red { color: #f14c4c; } .bright-green { color: #23d18b; }
+.bright-yellow { color: #f5f543; } .bright-blue { color: #3b8eea; }
+.bright-magenta { color: #d670d6; } .bright-cyan { color: #29b8db; }
+.bright-white { color: #ffffff; }
+.bold { font-weight: bold; } .strikethrough { text-decoration: line-through; }
+</style>
+</head>
+<body>
+"#;
+
+const TAIL: &str = "</body>\n</html>\n";
+
+/// Writes the steps in an HTML page, the top-level steps being expanded
+///
+/// The expressions are colored whether or not the terminal is, the colors being only forced
+/// while a step is written.
+pub struct HtmlOutput<'a, W: Write> {
+    writer: W,
+    options: &'a ViewOptions,
+    colors: bool,
+}
+impl<'a, W: Write> HtmlOutput<'a, W> {
+    /// writes the beginning of the page
+    pub fn new(mut writer: W, options: &'a ViewOptions) -> io::Result<Self> {
+        writer.write_all(HEAD.as_bytes())?;
+        Ok(Self {
+            writer,
+            options,
+            colors: true,
+        })
+    }
+    /// color the expressions, which is the default
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+    fn write_substep(&mut self, step: &StepNode, open: bool) -> io::Result<()> {
+        let count_str = match step.count_substeps() {
+            0 => String::new(),
+            1 => String::from(" <span class=\"count\">(1 substep)</span>"),
+            count => format!(" <span class=\"count\">({} substeps)</span>", count),
+        };
+        writeln!(
+            self.writer,
+            "<details{}><summary>{}{}</summary>",
+            if open { " open" } else { "" },
+            escape(&step.name),
+            count_str
+        )?;
+        if let Some(before) = &step.before {
+            self.write_state("before", before, node_html(before, self.options.long_form))?;
+        }
+        for part in &step.parts {
+            match part {
+                StepPart::State(name, state) => {
+                    let name = name.as_deref().unwrap_or("state");
+                    self.write_state(name, state, node_html(state, self.options.long_form))?;
+                }
+                StepPart::Substep(substep) => self.write_substep(substep, false)?,
+            }
+        }
+        if step.incomplete && !step.ends_with_incomplete_substep() {
+            writeln!(
+                self.writer,
+                "<div class=\"truncated\">log truncated here</div>"
+            )?;
+        }
+        if let Some(after) = &step.after {
+            let html = match &step.before {
+                Some(before) if self.options.diff => ansi_to_html(
+                    &TreeDiff::new(before, after)
+                        .decorated_print(after, self.options.long_form, &decorate)
+                        .to_string(),
+                ),
+                _ => node_html(after, self.options.long_form),
+            };
+            self.write_state("after", after, html)?;
+        }
+        writeln!(self.writer, "</details>")
+    }
+    /// writes the expression `node` of a step, displayed as `html`
+    ///
+    /// The long form of the expression is only written once, as the title of the label, since
+    /// repeating it for each node would make the page grow with the square of the depth.
+    fn write_state(&mut self, label: &str, node: &PoincareNode, html: String) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "<div class=\"state\"><span class=\"label\" title=\"{}\">{}:</span> {}</div>",
+            escape(&strip_colors(&node.pretty_print(0, true).to_string())),
+            escape(label),
+            html
+        )?;
+        if self.options.matrix_box {
+            for matrix in node.outermost_matrices() {
+                let lines = matrix
+                    .matrix_box(0, self.options.long_form)
+                    .unwrap_or_default();
+                let lines: Vec<String> = lines.iter().map(|line| ansi_to_html(line)).collect();
+                writeln!(
+                    self.writer,
+                    "<pre class=\"matrix\">{}</pre>",
+                    lines.join("\n")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+impl<'a, W: Write> Output for HtmlOutput<'a, W> {
+    fn write_step(&mut self, step: &StepNode) -> io::Result<()> {
+        let _colors = ColorOverride::set(self.colors);
+        self.write_substep(step, true)
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.write_all(TAIL.as_bytes())?;
+        self.writer.flush()
+    }
+}
+
+/// marks the output of each node, so that it becomes a `<span>` with a title
+fn decorate(node: &PoincareNode, _nesting_level: usize, output: ColoredString) -> ColoredString {
+    let attributes = node
+        .attributes
+        .as_ref()
+        .map(|attributes| format!(" {}", attributes.pretty_print()))
+        .unwrap_or_default();
+    format!(
+        "{}id {}: {}{}{}{}{}",
+        NODE_START, node.id, node.name, attributes, NODE_TITLE_END, output, NODE_END
+    )
+    .normal()
+}
+
+/// the expression `node` in HTML, where each node shows its id, name and attributes when hovered
+fn node_html(node: &PoincareNode, long_form: bool) -> String {
+    ansi_to_html(&node.decorated_print(0, long_form, &decorate).to_string())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The text style set by ANSI escape codes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Style {
+    /// class of one of the 16 colors of the terminal
+    color: Option<&'static str>,
+    /// RGB color, e.g. `#e69f00`, used by the themes with other colors
+    rgb: Option<String>,
+    bold: bool,
+    strikethrough: bool,
+}
+impl Style {
+    /// applies the codes of an escape sequence such as `1;33` or `38;2;230;159;0`
+    fn apply(&mut self, codes: &str) {
+        let mut codes = codes.split(';');
+        while let Some(code) = codes.next() {
+            match code {
+                "" | "0" => *self = Self::default(),
+                "1" => self.bold = true,
+                "9" => self.strikethrough = true,
+                "39" => {
+                    self.color = None;
+                    self.rgb = None;
+                }
+                "38" => match codes.next() {
+                    // the red, green and blue components
+                    Some("2") => {
+                        let components: Vec<u8> = codes
+                            .by_ref()
+                            .take(3)
+                            .filter_map(|component| component.parse().ok())
+                            .collect();
+                        if let [r, g, b] = components[..] {
+                            self.set_rgb(r, g, b);
+                        }
+                    }
+                    // a color of the 256-color palette
+                    Some("5") => {
+                        if let Some(index) = codes.next().and_then(|index| index.parse().ok()) {
+                            self.set_palette_color(index);
+                        }
+                    }
+                    _ => (),
+                },
+                code => {
+                    if let Some(color) = ansi_color(code) {
+                        self.color = Some(color);
+                        self.rgb = None;
+                    }
+                }
+            }
+        }
+    }
+    fn set_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.color = None;
+        self.rgb = Some(format!("#{:02x}{:02x}{:02x}", r, g, b));
+    }
+    /// sets the color `index` of the 256-color palette, whose first 16 colors are those of the
+    /// terminal, followed by a 6x6x6 cube and a gray ramp
+    fn set_palette_color(&mut self, index: u8) {
+        match index {
+            0..=7 => self.apply(&(30 + index).to_string()),
+            8..=15 => self.apply(&(90 + index - 8).to_string()),
+            16..=231 => {
+                let level = |n: u8| if n == 0 { 0 } else { 55 + 40 * n };
+                let cube = index - 16;
+                self.set_rgb(level(cube / 36), level(cube / 6 % 6), level(cube % 6));
+            }
+            232..=255 => {
+                let gray = 8 + 10 * (index - 232);
+                self.set_rgb(gray, gray, gray);
+            }
+        }
+    }
+    /// the attributes of a `<span>` with this style, e.g. ` class="yellow bold"`
+    fn attributes(&self) -> String {
+        let mut attributes = String::new();
+        let classes = self.classes();
+        if !classes.is_empty() {
+            attributes.push_str(&format!(" class=\"{}\"", classes.join(" ")));
+        }
+        if let Some(rgb) = &self.rgb {
+            attributes.push_str(&format!(" style=\"color: {}\"", rgb));
+        }
+        attributes
+    }
+    fn classes(&self) -> Vec<&'static str> {
+        let mut classes: Vec<&'static str> = self.color.into_iter().collect();
+        if self.bold {
+            classes.push("bold");
+        }
+        if self.strikethrough {
+            classes.push("strikethrough");
+        }
+        classes
+    }
+}
+
+fn ansi_color(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "30" => "black",
+        "31" => "red",
+        "32" => "green",
+        "33" => "yellow",
+        "34" => "blue",
+        "35" => "magenta",
+        "36" => "cyan",
+        "37" => "white",
+        "90" => "bright-black",
+        "91" => "bright-red",
+        "92" => "bright-green",
+        "93" => "bright-yellow",
+        "94" => "bright-blue",
+        "95" => "bright-magenta",
+        "96" => "bright-cyan",
+        "97" => "bright-white",
+        _ => return None,
+    })
+}
+
+/// turns the ANSI colors of `text` into `<span>`s with CSS classes, and the nodes marked by
+/// [`decorate`] into `<span>`s with a title
+///
+/// The colors are flat while the nodes are nested, so the colored `<span>`s are closed before
+/// the start or end of a node, and opened again after it.
+fn ansi_to_html(text: &str) -> String {
+    let mut html = String::new();
+    let mut style = Style::default();
+    let mut style_open = false;
+    let close_style = |html: &mut String, style_open: &mut bool| {
+        if *style_open {
+            html.push_str("</span>");
+            *style_open = false;
+        }
+    };
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                let mut codes = String::new();
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                    if c != '[' {
+                        codes.push(c);
+                    }
+                }
+                close_style(&mut html, &mut style_open);
+                style.apply(&codes);
+            }
+            NODE_START => {
+                close_style(&mut html, &mut style_open);
+                let title: String = chars
+                    .by_ref()
+                    .take_while(|c| *c != NODE_TITLE_END)
+                    .collect();
+                html.push_str(&format!(
+                    "<span class=\"node\" title=\"{}\">",
+                    escape(&title)
+                ));
+            }
+            NODE_END => {
+                close_style(&mut html, &mut style_open);
+                html.push_str("</span>");
+            }
+            c => {
+                if !style_open && style != Style::default() {
+                    html.push_str(&format!("<span{}>", style.attributes()));
+                    style_open = true;
+                }
+                html.push_str(&escape(&c.to_string()));
+            }
+        }
+    }
+    close_style(&mut html, &mut style_open);
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    fn style(codes: &str) -> Style {
+        let mut style = Style::default();
+        style.apply(codes);
+        style
+    }
+
+    fn rgb(rgb: &str) -> Style {
+        Style {
+            rgb: Some(String::from(rgb)),
+            ..Style::default()
+        }
+    }
+
+    #[test]
+    fn applies_the_colors_of_the_terminal() {
+        assert_eq!(
+            style("1;33"),
+            Style {
+                color: Some("yellow"),
+                bold: true,
+                ..Style::default()
+            }
+        );
+        assert_eq!(style("9").classes(), ["strikethrough"]);
+        assert_eq!(style("1;33;0"), Style::default());
+        assert_eq!(style("1;33;39").classes(), ["bold"]);
+    }
+
+    #[test]
+    fn applies_rgb_colors() {
+        assert_eq!(style("38;2;230;159;0"), rgb("#e69f00"));
+        assert_eq!(style("38;2;230;159;0;1").classes(), ["bold"]);
+        // the components are missing
+        assert_eq!(style("38;2;230"), Style::default());
+    }
+
+    #[test]
+    fn applies_the_colors_of_the_256_color_palette() {
+        assert_eq!(style("38;5;9").classes(), ["bright-red"]);
+        assert_eq!(style("38;5;0").classes(), ["black"]);
+        assert_eq!(style("38;5;208"), rgb("#ff8700"));
+        assert_eq!(style("38;5;16"), rgb("#000000"));
+        assert_eq!(style("38;5;232"), rgb("#080808"));
+        // the code after the color isn't taken as a component
+        assert_eq!(style("38;5;208;1").classes(), ["bold"]);
+    }
+
+    #[test]
+    fn turns_ansi_colors_into_spans() {
+        assert_eq!(ansi_to_html("a < b"), "a &lt; b");
+        assert_eq!(
+            ansi_to_html("\x1b[1;33mx\x1b[0m + y"),
+            "<span class=\"yellow bold\">x</span> + y"
+        );
+        assert_eq!(
+            ansi_to_html("\x1b[38;2;230;159;0mx\x1b[0m"),
+            "<span style=\"color: #e69f00\">x</span>"
+        );
+    }
+
+    #[test]
+    fn closes_the_colors_around_the_nodes() {
+        let text = format!(
+            "\x1b[33m({}id 1: Symbol x{}x{})\x1b[0m",
+            NODE_START, NODE_TITLE_END, NODE_END
+        );
+        assert_eq!(
+            ansi_to_html(&text),
+            "<span class=\"yellow\">(</span><span class=\"node\" title=\"id 1: Symbol x\">\
+             <span class=\"yellow\">x</span></span><span class=\"yellow\">)</span>"
+        );
+    }
+
+    /// the HTML page of a step whose expression before it is `-(-(…(x)))`, `depth` times
+    fn nested_opposites(depth: usize) -> String {
+        let mut expression = String::from(r#"<Symbol id="0" name="x"></Symbol>"#);
+        for id in 1..=depth {
+            expression = format!(r#"<Opposite id="{}">{}</Opposite>"#, id, expression);
+        }
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            expression
+        );
+        let step = parse_log(&log).unwrap().remove(0);
+        let options = ViewOptions::default();
+        let mut page = Vec::new();
+        let mut output = HtmlOutput::new(&mut page, &options).unwrap();
+        output.write_step(&step).unwrap();
+        output.finish().unwrap();
+        String::from_utf8(page).unwrap()
+    }
+
+    #[test]
+    fn titles_the_nodes_with_their_id_name_and_attributes() {
+        let page = nested_opposites(1);
+        assert!(page.contains("title=\"id 1: Opposite\""));
+        assert!(page.contains("title=\"id 0: Symbol x\""));
+        assert!(page.ends_with(TAIL));
+    }
+
+    #[test]
+    fn grows_linearly_with_the_depth() {
+        let (small, large) = (nested_opposites(40).len(), nested_opposites(80).len());
+        assert!(large < small * 5 / 2, "{} bytes, then {}", small, large);
+    }
+}
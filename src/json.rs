@@ -0,0 +1,284 @@
+//! JSON export of the steps, for other tools, following a versioned schema documented in the
+//! README.
+//!
+//! The schema version is [`SCHEMA_VERSION`], increased whenever a change could break the tools
+//! reading the export. Adding fields doesn't change the version.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::output::Output;
+use crate::poincare::{strip_colors, PoincareNode};
+use crate::reduction::{StepNode, StepPart};
+
+/// version of the schema of the JSON export
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonStep<'a> {
+    name: &'a str,
+    incomplete: bool,
+    before: Option<JsonExpression<'a>>,
+    after: Option<JsonExpression<'a>>,
+    parts: Vec<JsonPart<'a>>,
+}
+impl<'a> JsonStep<'a> {
+    fn new(step: &'a StepNode) -> Self {
+        Self {
+            name: &step.name,
+            incomplete: step.incomplete,
+            before: step.before.as_ref().map(JsonExpression::new),
+            after: step.after.as_ref().map(JsonExpression::new),
+            parts: step
+                .parts
+                .iter()
+                .map(|part| match part {
+                    StepPart::State(name, node) => JsonPart::State {
+                        name: name.as_deref(),
+                        expression: JsonExpression::new(node),
+                    },
+                    StepPart::Substep(substep) => JsonPart::Step(JsonStep::new(substep)),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonPart<'a> {
+    State {
+        name: Option<&'a str>,
+        expression: JsonExpression<'a>,
+    },
+    Step(JsonStep<'a>),
+}
+
+#[derive(Serialize)]
+struct JsonExpression<'a> {
+    /// the short form, without colors
+    short_form: String,
+    root: JsonNode<'a>,
+}
+impl<'a> JsonExpression<'a> {
+    fn new(node: &'a PoincareNode) -> Self {
+        Self {
+            short_form: strip_colors(&node.pretty_print(0, false).to_string()),
+            root: JsonNode::new(node),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    name: &'a str,
+    id: &'a str,
+    attributes: BTreeMap<&'static str, &'a str>,
+    children: Vec<JsonNode<'a>>,
+    incomplete: bool,
+}
+impl<'a> JsonNode<'a> {
+    fn new(node: &'a PoincareNode) -> Self {
+        Self {
+            name: &node.name,
+            id: &node.id,
+            attributes: node
+                .attributes
+                .iter()
+                .flat_map(|attributes| attributes.pairs())
+                .collect(),
+            children: node.children.iter().map(JsonNode::new).collect(),
+            incomplete: node.incomplete,
+        }
+    }
+}
+
+/// Writes the steps in JSON, either as a single document `{"version": 1, "steps": [...]}`, or as
+/// JSON Lines where each line is `{"version": 1, "step": {...}}`
+pub struct JsonOutput<W: Write> {
+    writer: W,
+    /// one line per top-level step, instead of a single document
+    lines: bool,
+    steps_written: usize,
+}
+impl<W: Write> JsonOutput<W> {
+    /// writes the beginning of the document, if the steps aren't written as JSON Lines
+    pub fn new(mut writer: W, lines: bool) -> io::Result<Self> {
+        if !lines {
+            write!(writer, "{{\"version\":{},\"steps\":[", SCHEMA_VERSION)?;
+        }
+        Ok(Self {
+            writer,
+            lines,
+            steps_written: 0,
+        })
+    }
+}
+impl<W: Write> Output for JsonOutput<W> {
+    fn write_step(&mut self, step: &StepNode) -> io::Result<()> {
+        if self.lines {
+            write!(self.writer, "{{\"version\":{},\"step\":", SCHEMA_VERSION)?;
+        } else {
+            // one top-level step per line in the document too, to read it more easily
+            let separator = if self.steps_written > 0 { "," } else { "" };
+            writeln!(self.writer, "{}", separator)?;
+        }
+        serde_json::to_writer(&mut self.writer, &JsonStep::new(step))?;
+        if self.lines {
+            writeln!(self.writer, "}}")?;
+        }
+        self.steps_written += 1;
+        Ok(())
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        if !self.lines {
+            writeln!(self.writer, "\n]}}")?;
+        }
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::parse_log;
+
+    const LOG: &str = r#"<Step name="expand">
+  <State name="before"><Symbol id="5" name="y"></Symbol></State>
+  <State name="after"><Symbol id="5" name="y"></Symbol></State>
+</Step>
+<Step name="simplify">
+  <State name="before"><Opposite id="1"><Rational id="2" negative="0" numerator="1" denominator="2"></Rational></Opposite></State>
+  <State name="sign"><Rational id="2" negative="1" numerator="1" denominator="2"></Rational></State>
+  <Step name="shallowReduce">
+    <State name="before"><Power id="3"><Symbol id="4" name="x"></Symbol>"#;
+
+    fn export(lines: bool) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut output = JsonOutput::new(&mut buffer, lines).unwrap();
+        for step in parse_log(LOG).unwrap() {
+            output.write_step(&step).unwrap();
+        }
+        output.finish().unwrap();
+        buffer
+    }
+
+    fn first_step() -> Value {
+        let y = json!({
+            "short_form": "y",
+            "root": {
+                "name": "Symbol",
+                "id": "5",
+                "attributes": {"name": "y"},
+                "children": [],
+                "incomplete": false,
+            },
+        });
+        json!({"name": "expand", "incomplete": false, "before": y, "after": y, "parts": []})
+    }
+
+    /// the truncated step, with its intermediate state and substep
+    fn step() -> Value {
+        json!({
+            "name": "simplify",
+            "incomplete": true,
+            "before": {
+                "short_form": "-1/2",
+                "root": {
+                    "name": "Opposite",
+                    "id": "1",
+                    "attributes": {},
+                    "children": [{
+                        "name": "Rational",
+                        "id": "2",
+                        "attributes": {"negative": "0", "numerator": "1", "denominator": "2"},
+                        "children": [],
+                        "incomplete": false,
+                    }],
+                    "incomplete": false,
+                },
+            },
+            "after": null,
+            "parts": [
+                {
+                    "type": "state",
+                    "name": "sign",
+                    "expression": {
+                        "short_form": "-1/2",
+                        "root": {
+                            "name": "Rational",
+                            "id": "2",
+                            "attributes": {"negative": "1", "numerator": "1", "denominator": "2"},
+                            "children": [],
+                            "incomplete": false,
+                        },
+                    },
+                },
+                {
+                    "type": "step",
+                    "name": "shallowReduce",
+                    "incomplete": true,
+                    "before": {
+                        "short_form": "x ^ \u{2026}",
+                        "root": {
+                            "name": "Power",
+                            "id": "3",
+                            "attributes": {},
+                            "children": [{
+                                "name": "Symbol",
+                                "id": "4",
+                                "attributes": {"name": "x"},
+                                "children": [],
+                                "incomplete": false,
+                            }],
+                            "incomplete": true,
+                        },
+                    },
+                    "after": null,
+                    "parts": [],
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn exports_a_document() {
+        let document: Value = serde_json::from_slice(&export(false)).unwrap();
+        assert_eq!(
+            document,
+            json!({"version": SCHEMA_VERSION, "steps": [first_step(), step()]})
+        );
+    }
+
+    #[test]
+    fn exports_a_line_per_step() {
+        let buffer = export(true);
+        let lines: Vec<Value> = String::from_utf8(buffer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                json!({"version": SCHEMA_VERSION, "step": first_step()}),
+                json!({"version": SCHEMA_VERSION, "step": step()}),
+            ]
+        );
+    }
+
+    #[test]
+    fn exports_an_empty_document() {
+        let mut buffer = Vec::new();
+        JsonOutput::new(&mut buffer, false)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let document: Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(document, json!({"version": 1, "steps": []}));
+    }
+}
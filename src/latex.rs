@@ -0,0 +1,825 @@
+//! LaTeX form of the Poincare nodes, e.g. `\frac{1}{2}` for a `Division`, and LaTeX export of
+//! the steps, to be compiled with e.g. `pdflatex`.
+//!
+//! Like the short form, operands are only put in parentheses when they would be read differently
+//! without them, following the [`Precedence`] levels of the short form.
+
+use std::io::{self, Write};
+
+use crate::output::Output;
+use crate::poincare::{PoincareAttributes, PoincareNode};
+use crate::reduction::{StepNode, StepPart};
+use crate::render::{Associativity, Notation, Precedence};
+
+const PREAMBLE: &str = r"\documentclass{article}
+\usepackage[margin=2cm]{geometry}
+\usepackage{amsmath}
+\usepackage{amssymb}
+\allowdisplaybreaks
+\begin{document}
+";
+
+const END: &str = "\\end{document}\n";
+
+/// The LaTeX form of a node
+struct Latex {
+    text: String,
+    /// how tightly the LaTeX form binds its parts together
+    precedence: u8,
+    /// the node is an n-ary operator
+    nary: bool,
+}
+impl Latex {
+    fn new(text: String, precedence: u8) -> Self {
+        Self {
+            text,
+            precedence,
+            nary: false,
+        }
+    }
+    fn atom(text: String) -> Self {
+        Self::new(text, Precedence::ATOM)
+    }
+    fn parenthesized_if(&self, parentheses: bool) -> String {
+        if parentheses {
+            parenthesized(&self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// LaTeX form of `node`, see [`PoincareNode::latex`]
+pub(crate) fn latex(node: &PoincareNode) -> String {
+    to_latex(node).text
+}
+
+fn to_latex(node: &PoincareNode) -> Latex {
+    let mut children: Vec<Latex> = node.children.iter().map(to_latex).collect();
+    if node.incomplete {
+        // the children missing from a truncated log, like in the short form
+        children.push(Latex::atom(String::from("\\ldots")));
+    }
+    known_latex(node, &children).unwrap_or_else(|| {
+        // unknown node, or known node whose children don't match, e.g. in a truncated log
+        let mut text = format!("\\mathrm{{{}}}", escape_math(&node.name));
+        if !children.is_empty() {
+            text.push_str(&arguments(&children));
+        }
+        Latex::atom(text)
+    })
+}
+
+/// LaTeX form of `node` if it's a known node, `children` being the LaTeX forms of its children
+fn known_latex(node: &PoincareNode, children: &[Latex]) -> Option<Latex> {
+    use Associativity::*;
+    use Notation::*;
+    let operator = |symbol: &str, notation, precedence, associativity| {
+        operator(children, symbol, notation, precedence, associativity)
+    };
+    Some(match (node.name.as_str(), children) {
+        (
+            "Symbol" | "SymbolAbstract" | "Sequence" | "Function" | "Constant" | "BasedInteger"
+            | "Decimal" | "Float" | "Integer" | "Rational" | "Infinity" | "Boolean" | "Unit"
+            | "CodePointLayout",
+            _,
+        ) => {
+            let mut latex = attributes_latex(node.attributes.as_ref()?);
+            if !children.is_empty() {
+                // e.g. `f(x)`
+                latex = Latex::atom(format!("{}{}", latex.text, arguments(children)));
+            }
+            latex
+        }
+        ("Store" | "UnitConvert", _) => operator("\\to", Infix, Precedence::STORE, Left)?,
+        ("Equal", _) => operator("=", Infix, Precedence::RELATION, Left)?,
+        ("Comparison", _) => {
+            let symbol = match &node.attributes {
+                Some(PoincareAttributes::Comparison { operator }) => comparison(operator),
+                _ => String::from("?"),
+            };
+            operator(&symbol, Infix, Precedence::RELATION, Left)?
+        }
+        ("Addition", _) => operator("+", Infix, Precedence::ADDITIVE, Nary)?,
+        ("Subtraction", _) => operator("-", Infix, Precedence::ADDITIVE, Left)?,
+        ("Opposite", _) => operator("-", Prefix, Precedence::OPPOSITE, Right)?,
+        ("Multiplication", _) => operator("\\times", Infix, Precedence::MULTIPLICATIVE, Nary)?,
+        ("Factorial", _) => operator("!", Postfix, Precedence::POSTFIX, Left)?,
+        ("PercentSimple", _) => operator("\\%", Postfix, Precedence::POSTFIX, Left)?,
+        ("LogicalOperatorNot", _) => operator("\\neg ", Prefix, Precedence::OPPOSITE, Right)?,
+        ("Division" | "FractionLayout", [numerator, denominator]) => Latex::new(
+            format!("\\frac{{{}}}{{{}}}", numerator.text, denominator.text),
+            Precedence::MULTIPLICATIVE,
+        ),
+        ("Power", [base, exponent]) => Latex::new(
+            format!(
+                "{{{}}}^{{{}}}",
+                base.parenthesized_if(base.precedence <= Precedence::POWER),
+                exponent.text
+            ),
+            Precedence::POWER,
+        ),
+        ("SquareRoot", [radicand]) | ("NthRootLayout", [radicand]) => {
+            Latex::atom(format!("\\sqrt{{{}}}", radicand.text))
+        }
+        ("NthRoot" | "NthRootLayout", [radicand, index]) => {
+            Latex::atom(format!("\\sqrt[{}]{{{}}}", index.text, radicand.text))
+        }
+        ("AbsoluteValue" | "AbsoluteValueLayout", [value]) => {
+            Latex::atom(format!("\\left|{}\\right|", value.text))
+        }
+        ("VectorNorm" | "VectorNormLayout", [value]) => {
+            Latex::atom(format!("\\left\\|{}\\right\\|", value.text))
+        }
+        ("Floor" | "FloorLayout", [value]) => {
+            Latex::atom(format!("\\left\\lfloor {}\\right\\rfloor", value.text))
+        }
+        ("Ceiling" | "CeilingLayout", [value]) => {
+            Latex::atom(format!("\\left\\lceil {}\\right\\rceil", value.text))
+        }
+        ("Conjugate" | "ConjugateLayout", [value]) => {
+            Latex::atom(format!("\\overline{{{}}}", value.text))
+        }
+        ("Logarithm", [_, base]) => Latex::atom(format!(
+            "\\log_{{{}}}{}",
+            base.text,
+            arguments(&children[..1])
+        )),
+        ("BinomialCoefficient" | "BinomialCoefficientLayout", [n, k]) => {
+            Latex::atom(format!("\\binom{{{}}}{{{}}}", n.text, k.text))
+        }
+        ("MatrixTranspose", [matrix]) => Latex::new(
+            format!(
+                "{{{}}}^{{T}}",
+                matrix.parenthesized_if(matrix.precedence <= Precedence::POWER)
+            ),
+            Precedence::POWER,
+        ),
+        ("MatrixInverse", [matrix]) => Latex::new(
+            format!(
+                "{{{}}}^{{-1}}",
+                matrix.parenthesized_if(matrix.precedence <= Precedence::POWER)
+            ),
+            Precedence::POWER,
+        ),
+        ("Sum" | "Product", [term, variable, start, end]) => Latex::new(
+            format!(
+                "{}_{{{}={}}}^{{{}}}{}",
+                if node.name == "Sum" {
+                    "\\sum"
+                } else {
+                    "\\prod"
+                },
+                variable.text,
+                start.text,
+                end.text,
+                term.parenthesized_if(term.precedence <= Precedence::ADDITIVE)
+            ),
+            Precedence::MULTIPLICATIVE,
+        ),
+        ("Integral", [integrand, variable, lower, upper]) => Latex::new(
+            format!(
+                "\\int_{{{}}}^{{{}}}{}\\,\\mathrm{{d}}{}",
+                lower.text,
+                upper.text,
+                integrand.parenthesized_if(integrand.precedence <= Precedence::ADDITIVE),
+                variable.text
+            ),
+            Precedence::MULTIPLICATIVE,
+        ),
+        ("Derivative", [function, variable, value]) => Latex::atom(format!(
+            "\\left.\\frac{{\\mathrm{{d}}}}{{\\mathrm{{d}}{}}}{}\\right|_{{{}={}}}",
+            variable.text,
+            parenthesized(&function.text),
+            variable.text,
+            value.text
+        )),
+        ("Matrix" | "MatrixLayout", _) => Latex::atom(matrix(node, children)?),
+        ("Parenthesis" | "ParenthesisLayout", [value]) => Latex::atom(parenthesized(&value.text)),
+        ("CurlyBraceLayout", [value]) => {
+            Latex::atom(format!("\\left\\{{{}\\right\\}}", value.text))
+        }
+        ("List", _) => Latex::atom(format!("\\left\\{{{}\\right\\}}", joined(children))),
+        ("Point", [_, _]) => Latex::atom(parenthesized(&joined(children))),
+        ("ListElement", [list, index]) => {
+            Latex::atom(format!("{}{}", list.text, parenthesized(&index.text)))
+        }
+        ("ListSlice", [list, _, _]) => {
+            Latex::atom(format!("{}{}", list.text, arguments(&children[1..])))
+        }
+        ("PercentAddition", [value, percentage]) => Latex::new(
+            format!("{}\\nearrow {}\\%", value.text, percentage.text),
+            Precedence::ADDITIVE,
+        ),
+        ("MixedFraction", [integer, fraction]) => {
+            Latex::atom(format!("{}{}", integer.text, fraction.text))
+        }
+        ("HorizontalLayout", _) => Latex::new(
+            children.iter().map(|child| child.text.as_str()).collect(),
+            if children.len() == 1 {
+                children[0].precedence
+            } else {
+                Precedence::MULTIPLICATIVE
+            },
+        ),
+        ("VerticalOffsetLayout", [offset]) => {
+            let position = match &node.attributes {
+                Some(PoincareAttributes::VerticalOffsetLayout { position })
+                    if position == "subscript" =>
+                {
+                    "_"
+                }
+                _ => "^",
+            };
+            // the base is the previous layout in the concatenation
+            Latex::new(
+                format!("{}{{{}}}", position, offset.text),
+                Precedence::POSTFIX,
+            )
+        }
+        ("CondensedSumLayout", [base, subscript, superscript]) => Latex::atom(format!(
+            "{{{}}}_{{{}}}^{{{}}}",
+            base.text, subscript.text, superscript.text
+        )),
+        (name, _) => {
+            if let Some(text) = plain_name(name) {
+                return Some(Latex::atom(String::from(text)));
+            }
+            let function = function(name)?;
+            Latex::atom(format!("{}{}", function, arguments(children)))
+        }
+    })
+}
+
+/// LaTeX form of an operator, the operands being put in parentheses only when they would be read
+/// differently without them, as in [`crate::render::OperatorRenderer`]
+fn operator(
+    children: &[Latex],
+    symbol: &str,
+    notation: Notation,
+    precedence: u8,
+    associativity: Associativity,
+) -> Option<Latex> {
+    let needs_parentheses = |child_n: usize, child: &Latex| {
+        if child.precedence != precedence {
+            return child.precedence < precedence;
+        }
+        match (notation, associativity) {
+            (Notation::Prefix, _) | (Notation::Postfix, _) => true,
+            (_, Associativity::Nary) => child_n > 0 || child.nary,
+            (_, Associativity::Left) => child_n > 0,
+            (_, Associativity::Right) => child_n == 0,
+        }
+    };
+    let operand = |child_n: usize| {
+        let child = &children[child_n];
+        child.parenthesized_if(needs_parentheses(child_n, child))
+    };
+    let text = match notation {
+        Notation::Infix if !children.is_empty() => {
+            let operands: Vec<String> = (0..children.len()).map(operand).collect();
+            operands.join(&format!(" {} ", symbol))
+        }
+        Notation::Prefix if children.len() == 1 => format!("{}{}", symbol, operand(0)),
+        Notation::Postfix if children.len() == 1 => format!("{}{}", operand(0), symbol),
+        _ => return None,
+    };
+    Some(Latex {
+        text,
+        precedence,
+        nary: associativity == Associativity::Nary,
+    })
+}
+
+fn attributes_latex(attributes: &PoincareAttributes) -> Latex {
+    let sign = |negative: &str| match negative {
+        "0" => "",
+        "1" => "-",
+        _ => "\\pm",
+    };
+    let signed_precedence = |negative: &str, precedence| {
+        if negative == "0" {
+            precedence
+        } else {
+            Precedence::OPPOSITE
+        }
+    };
+    match attributes {
+        PoincareAttributes::BasedInteger { base, integer } if base == "10" => {
+            Latex::atom(escape_math(integer))
+        }
+        PoincareAttributes::BasedInteger { base, integer } => Latex::atom(format!(
+            "{{{}}}_{{{}}}",
+            escape_math(integer),
+            escape_math(base)
+        )),
+        PoincareAttributes::Boolean { value } => {
+            Latex::atom(format!("\\mathrm{{{}}}", escape_math(value)))
+        }
+        PoincareAttributes::Decimal {
+            negative,
+            mantissa,
+            exponent,
+        } => Latex::new(
+            format!(
+                "{}{}\\times 10^{{{}}}",
+                sign(negative),
+                escape_math(mantissa),
+                escape_math(exponent)
+            ),
+            signed_precedence(negative, Precedence::MULTIPLICATIVE),
+        ),
+        PoincareAttributes::Infinity { negative } => Latex::new(
+            format!("{}\\infty", sign(negative)),
+            signed_precedence(negative, Precedence::ATOM),
+        ),
+        PoincareAttributes::Rational {
+            negative,
+            numerator,
+            denominator,
+        } if denominator == "1" => Latex::new(
+            format!("{}{}", sign(negative), escape_math(numerator)),
+            signed_precedence(negative, Precedence::ATOM),
+        ),
+        PoincareAttributes::Rational {
+            negative,
+            numerator,
+            denominator,
+        } => Latex::new(
+            format!(
+                "{}\\frac{{{}}}{{{}}}",
+                sign(negative),
+                escape_math(numerator),
+                escape_math(denominator)
+            ),
+            signed_precedence(negative, Precedence::MULTIPLICATIVE),
+        ),
+        PoincareAttributes::SymbolAbstract { name } => Latex::atom(symbol(name)),
+        PoincareAttributes::Unit {
+            prefix,
+            root_symbol,
+        } => Latex::atom(format!(
+            "\\mathrm{{{}}}",
+            escape_math(&format!("{}{}", prefix, root_symbol))
+        )),
+        PoincareAttributes::Float { value } | PoincareAttributes::Integer { value }
+            if value.starts_with('-') =>
+        {
+            Latex::new(escape_math(value), Precedence::OPPOSITE)
+        }
+        attributes => Latex::atom(escape_math(&attributes.pretty_print())),
+    }
+}
+
+/// a symbol in italics if it's a single letter, upright otherwise, e.g. `\mathrm{ans}`
+fn symbol(name: &str) -> String {
+    let escaped = escape_math(name);
+    if name.chars().count() > 1 && name.chars().all(|c| c.is_alphanumeric()) {
+        format!("\\mathrm{{{}}}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn comparison(operator: &str) -> String {
+    String::from(match operator {
+        "<=" | "\u{2264}" => "\\leq",
+        ">=" | "\u{2265}" => "\\geq",
+        "!=" | "\u{2260}" => "\\neq",
+        "<" => "<",
+        ">" => ">",
+        "=" | "==" => "=",
+        operator => return escape_math(operator),
+    })
+}
+
+/// the nodes displayed as a fixed text, see [`crate::render::NameRenderer`]
+fn plain_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Undefined" => "\\mathrm{undef}",
+        "Nonreal" => "\\mathrm{nonreal}",
+        "EmptyExpression" | "EmptyLayout" => "\\square",
+        "Ghost" => "\\mathrm{ghost}",
+        "LeftParenthesisLayout" => "(",
+        "RightParenthesisLayout" => ")",
+        "LeftSquareBracketLayout" => "[",
+        "RightSquareBracketLayout" => "]",
+        "LeftCurlyBraceLayout" => "\\{",
+        "RightCurlyBraceLayout" => "\\}",
+        _ => return None,
+    })
+}
+
+/// the command displaying the name of a function, applied to arguments in parentheses
+fn function(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Sine" => "\\sin",
+        "Cosine" => "\\cos",
+        "Tangent" => "\\tan",
+        "ArcSine" => "\\arcsin",
+        "ArcCosine" => "\\arccos",
+        "ArcTangent" => "\\arctan",
+        "Cosecant" => "\\csc",
+        "Secant" => "\\sec",
+        "Cotangent" => "\\cot",
+        "ArcCosecant" => "\\operatorname{arccsc}",
+        "ArcSecant" => "\\operatorname{arcsec}",
+        "ArcCotangent" => "\\operatorname{arccot}",
+        "HyperbolicSine" => "\\sinh",
+        "HyperbolicCosine" => "\\cosh",
+        "HyperbolicTangent" => "\\tanh",
+        "HyperbolicArcSine" => "\\operatorname{arsinh}",
+        "HyperbolicArcCosine" => "\\operatorname{arcosh}",
+        "HyperbolicArcTangent" => "\\operatorname{artanh}",
+        "NaperianLogarithm" => "\\ln",
+        "Logarithm" => "\\log",
+        "SquareRoot" => "\\operatorname{sqrt}",
+        "NthRoot" => "\\operatorname{root}",
+        "AbsoluteValue" => "\\operatorname{abs}",
+        "ComplexArgument" => "\\arg",
+        "RealPart" => "\\operatorname{Re}",
+        "ImaginaryPart" => "\\operatorname{Im}",
+        "Conjugate" => "\\operatorname{conj}",
+        "Floor" => "\\operatorname{floor}",
+        "Ceiling" => "\\operatorname{ceil}",
+        "FracPart" => "\\operatorname{frac}",
+        "Round" => "\\operatorname{round}",
+        "SignFunction" => "\\operatorname{sign}",
+        "GreatCommonDivisor" => "\\gcd",
+        "LeastCommonMultiple" => "\\operatorname{lcm}",
+        "DivisionQuotient" => "\\operatorname{quo}",
+        "DivisionRemainder" => "\\operatorname{rem}",
+        "Factor" => "\\operatorname{factor}",
+        "BinomialCoefficient" => "\\operatorname{binomial}",
+        "PermuteCoefficient" => "\\operatorname{permute}",
+        "Random" => "\\operatorname{rand}",
+        "Randint" => "\\operatorname{randint}",
+        "BinomCDF" => "\\operatorname{binomcdf}",
+        "BinomPDF" => "\\operatorname{binompdf}",
+        "InvBinom" => "\\operatorname{invbinom}",
+        "InvNorm" => "\\operatorname{invnorm}",
+        "NormCDF" => "\\operatorname{normcdf}",
+        "NormCDF2" => "\\operatorname{normcdf2}",
+        "NormPDF" => "\\operatorname{normpdf}",
+        "ConfidenceInterval" => "\\operatorname{confidence}",
+        "PredictionInterval" => "\\operatorname{prediction95}",
+        "SimplePredictionInterval" => "\\operatorname{prediction}",
+        "Sum" | "SumLayout" | "ListSum" => "\\operatorname{sum}",
+        "Product" | "ProductLayout" => "\\operatorname{product}",
+        "Integral" | "IntegralLayout" => "\\operatorname{int}",
+        "Derivative" => "\\operatorname{diff}",
+        "Determinant" => "\\det",
+        "MatrixDimension" => "\\dim",
+        "MatrixIdentity" => "\\operatorname{identity}",
+        "MatrixInverse" => "\\operatorname{inverse}",
+        "MatrixTrace" => "\\operatorname{tr}",
+        "MatrixTranspose" => "\\operatorname{transpose}",
+        "MatrixRowEchelonForm" => "\\operatorname{ref}",
+        "MatrixReducedRowEchelonForm" => "\\operatorname{rref}",
+        "VectorCross" => "\\operatorname{cross}",
+        "VectorDot" => "\\operatorname{dot}",
+        "VectorNorm" => "\\operatorname{norm}",
+        "Dependency" => "\\operatorname{dep}",
+        "ListMaximum" => "\\max",
+        "ListMinimum" => "\\min",
+        "ListMean" => "\\operatorname{mean}",
+        "ListMedian" => "\\operatorname{med}",
+        "ListProduct" => "\\operatorname{prod}",
+        "ListSampleStandardDeviation" => "\\operatorname{samplestddev}",
+        "ListSequence" => "\\operatorname{sequence}",
+        "ListSort" => "\\operatorname{sort}",
+        "ListStandardDeviation" => "\\operatorname{stddev}",
+        "ListVariance" => "\\operatorname{var}",
+        "PiecewiseOperator" => "\\operatorname{piecewise}",
+        _ => return None,
+    })
+}
+
+/// a `pmatrix` with the children of a matrix, or `None` if their number doesn't match its
+/// dimensions
+fn matrix(node: &PoincareNode, children: &[Latex]) -> Option<String> {
+    let (rows, columns) = node.matrix_dimensions()?;
+    if rows == 0 || columns == 0 || rows * columns != children.len() {
+        return None;
+    }
+    let rows: Vec<String> = children
+        .chunks(columns)
+        .map(|row| {
+            let cells: Vec<&str> = row.iter().map(|cell| cell.text.as_str()).collect();
+            cells.join(" & ")
+        })
+        .collect();
+    Some(format!(
+        "\\begin{{pmatrix}}{}\\end{{pmatrix}}",
+        rows.join(" \\\\ ")
+    ))
+}
+
+fn parenthesized(text: &str) -> String {
+    format!("\\left({}\\right)", text)
+}
+
+fn joined(children: &[Latex]) -> String {
+    let texts: Vec<&str> = children.iter().map(|child| child.text.as_str()).collect();
+    texts.join(", ")
+}
+
+/// the arguments of a function, e.g. `\left(x, y\right)`
+fn arguments(children: &[Latex]) -> String {
+    parenthesized(&joined(children))
+}
+
+/// `text` where the characters which have a meaning in LaTeX math mode are escaped, and the
+/// usual non-ASCII symbols are replaced by their command
+fn escape_math(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\backslash "),
+            '{' | '}' | '_' | '#' | '$' | '%' | '&' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\sim "),
+            '^' => escaped.push_str("\\wedge "),
+            '\u{3c0}' => escaped.push_str("\\pi "),
+            '\u{3b8}' => escaped.push_str("\\theta "),
+            '\u{212f}' => escaped.push('e'),
+            '\u{1d422}' => escaped.push('i'),
+            '\u{221e}' => escaped.push_str("\\infty "),
+            '\u{221a}' => escaped.push_str("\\surd "),
+            '\u{d7}' => escaped.push_str("\\times "),
+            '\u{b7}' => escaped.push_str("\\cdot "),
+            '\u{2264}' => escaped.push_str("\\leq "),
+            '\u{2265}' => escaped.push_str("\\geq "),
+            '\u{2260}' => escaped.push_str("\\neq "),
+            '\u{2192}' => escaped.push_str("\\to "),
+            '\u{2212}' => escaped.push('-'),
+            '\u{b0}' => escaped.push_str("^{\\circ}"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `text` where the characters which have a meaning in LaTeX text mode are escaped, as well as
+/// those which the default OT1 font encoding prints as other characters, e.g. `<` as `¡`
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '_' | '#' | '$' | '%' | '&' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            '|' => escaped.push_str("\\textbar{}"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes the steps in a LaTeX document, where each top-level step is a section listing the
+/// expressions before and after it and its substeps as aligned equations
+pub struct LatexOutput<W: Write> {
+    writer: W,
+}
+impl<W: Write> LatexOutput<W> {
+    /// writes the preamble of the document
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(PREAMBLE.as_bytes())?;
+        Ok(Self { writer })
+    }
+}
+impl<W: Write> Output for LatexOutput<W> {
+    fn write_step(&mut self, step: &StepNode) -> io::Result<()> {
+        writeln!(self.writer, "\\section*{{{}}}", escape_text(&step.name))?;
+        let mut rows = Vec::new();
+        push_rows(step, 0, &mut rows);
+        if rows.is_empty() {
+            // an empty `align*` doesn't compile
+            writeln!(self.writer, "No expression.")?;
+        } else {
+            writeln!(self.writer, "\\begin{{align*}}")?;
+            writeln!(self.writer, "{}", rows.join(" \\\\\n"))?;
+            writeln!(self.writer, "\\end{{align*}}")?;
+        }
+        writeln!(self.writer)
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.write_all(END.as_bytes())?;
+        self.writer.flush()
+    }
+}
+
+/// adds the rows `name && before &\to after` of `step` and its substeps to `rows`, the names
+/// being indented by `depth`
+fn push_rows(step: &StepNode, depth: usize, rows: &mut Vec<String>) {
+    if step.before.is_some() || step.after.is_some() {
+        let expression = |node: &Option<PoincareNode>| match node {
+            Some(node) => node.latex(),
+            None => String::from("?"),
+        };
+        rows.push(format!(
+            "\\text{{{}{}}} && {} &\\to {}",
+            "\\quad ".repeat(depth),
+            escape_text(&step.name),
+            expression(&step.before),
+            expression(&step.after)
+        ));
+    }
+    for part in &step.parts {
+        if let StepPart::Substep(substep) = part {
+            push_rows(substep, depth + 1, rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    /// LaTeX form of the expression written in XML as `xml`
+    fn latex(xml: &str) -> String {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            xml
+        );
+        parse_log(&log).unwrap().remove(0).before.unwrap().latex()
+    }
+
+    fn node(name: &str, children: &[String]) -> String {
+        format!(r#"<{0} id="{0}">{1}</{0}>"#, name, children.concat())
+    }
+
+    fn symbol(name: &str) -> String {
+        format!(r#"<Symbol id="s" name="{}"></Symbol>"#, name)
+    }
+
+    fn rational(numerator: u32, denominator: u32) -> String {
+        format!(
+            r#"<Rational id="r" negative="0" numerator="{}" denominator="{}"></Rational>"#,
+            numerator, denominator
+        )
+    }
+
+    #[test]
+    fn writes_fractions() {
+        assert_eq!(latex(&rational(1, 2)), r"\frac{1}{2}");
+        let sum = node("Addition", &[symbol("x"), rational(1, 1)]);
+        let division = node("Division", &[sum, symbol("y")]);
+        assert_eq!(latex(&division), r"\frac{x + 1}{y}");
+        assert_eq!(
+            latex(&node("Division", &[division, rational(2, 1)])),
+            r"\frac{\frac{x + 1}{y}}{2}"
+        );
+    }
+
+    #[test]
+    fn writes_roots() {
+        assert_eq!(latex(&node("SquareRoot", &[symbol("x")])), r"\sqrt{x}");
+        assert_eq!(
+            latex(&node("NthRoot", &[symbol("x"), rational(3, 1)])),
+            r"\sqrt[3]{x}"
+        );
+    }
+
+    #[test]
+    fn parenthesizes_the_bases_of_powers() {
+        assert_eq!(
+            latex(&node("Power", &[symbol("x"), rational(2, 1)])),
+            "{x}^{2}"
+        );
+        let sum = node("Addition", &[symbol("x"), rational(1, 1)]);
+        assert_eq!(
+            latex(&node("Power", &[sum, rational(2, 1)])),
+            r"{\left(x + 1\right)}^{2}"
+        );
+        assert_eq!(
+            latex(&node("Power", &[rational(1, 2), symbol("n")])),
+            r"{\left(\frac{1}{2}\right)}^{n}"
+        );
+        let power = node("Power", &[symbol("x"), symbol("y")]);
+        assert_eq!(
+            latex(&node("Power", &[power.clone(), symbol("z")])),
+            r"{\left({x}^{y}\right)}^{z}"
+        );
+        // the exponent is grouped by the braces
+        assert_eq!(
+            latex(&node("Power", &[symbol("z"), power])),
+            "{z}^{{x}^{y}}"
+        );
+    }
+
+    #[test]
+    fn writes_absolute_values_and_matrices() {
+        let difference = node("Subtraction", &[symbol("x"), symbol("y")]);
+        assert_eq!(
+            latex(&node("AbsoluteValue", &[difference])),
+            r"\left|x - y\right|"
+        );
+        let matrix = format!(
+            r#"<Matrix id="m" rows="2" columns="2">{}{}{}{}</Matrix>"#,
+            rational(1, 1),
+            symbol("x"),
+            rational(1, 2),
+            rational(4, 1)
+        );
+        assert_eq!(
+            latex(&matrix),
+            r"\begin{pmatrix}1 & x \\ \frac{1}{2} & 4\end{pmatrix}"
+        );
+        // children not matching the dimensions
+        let matrix = format!(
+            r#"<Matrix id="m" rows="2" columns="2">{}</Matrix>"#,
+            symbol("x")
+        );
+        assert_eq!(latex(&matrix), r"\mathrm{Matrix}\left(x\right)");
+    }
+
+    #[test]
+    fn writes_functions() {
+        assert_eq!(
+            latex(&node("Cosine", &[symbol("x")])),
+            r"\cos\left(x\right)"
+        );
+        assert_eq!(
+            latex(&node("ArcSecant", &[symbol("x")])),
+            r"\operatorname{arcsec}\left(x\right)"
+        );
+        assert_eq!(
+            latex(&node("GreatCommonDivisor", &[symbol("a"), symbol("b")])),
+            r"\gcd\left(a, b\right)"
+        );
+        assert_eq!(
+            latex(&node("Logarithm", &[symbol("x"), rational(2, 1)])),
+            r"\log_{2}\left(x\right)"
+        );
+        assert_eq!(
+            latex(&node("Unknown", &[symbol("x")])),
+            r"\mathrm{Unknown}\left(x\right)"
+        );
+    }
+
+    #[test]
+    fn escapes_symbols() {
+        assert_eq!(latex(&symbol("ans")), r"\mathrm{ans}");
+        assert_eq!(latex(&symbol("x_1")), r"x\_1");
+        assert_eq!(latex(&symbol("a^b")), r"a\wedge b");
+        assert_eq!(latex(&symbol("&amp;%#")), r"\&\%\#");
+        assert_eq!(latex(&symbol("\u{3c0}")), r"\pi ");
+    }
+
+    /// the LaTeX document of the step `name`, from `x` to `y`
+    fn document(name: &str) -> String {
+        let log = format!(
+            r#"<Step name="{}"><State name="before">{}</State><State name="after">{}</State></Step>"#,
+            name,
+            symbol("x"),
+            symbol("y")
+        );
+        let mut buffer = Vec::new();
+        let mut output = LatexOutput::new(&mut buffer).unwrap();
+        for step in parse_log(&log).unwrap() {
+            output.write_step(&step).unwrap();
+        }
+        output.finish().unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn writes_a_section_per_step() {
+        let expected = format!(
+            r"{}\section*{{simplify}}
+\begin{{align*}}
+\text{{simplify}} && x &\to y
+\end{{align*}}
+
+\end{{document}}
+",
+            PREAMBLE
+        );
+        assert_eq!(document("simplify"), expected);
+    }
+
+    #[test]
+    fn escapes_the_step_names() {
+        assert_eq!(
+            escape_text(r"a_b^c & 100% #1 \ {x} ~"),
+            r"a\_b\textasciicircum{}c \& 100\% \#1 \textbackslash{} \{x\} \textasciitilde{}"
+        );
+        assert_eq!(
+            escape_text("a<b>c|d"),
+            r"a\textless{}b\textgreater{}c\textbar{}d"
+        );
+        let latex = document("shallow_reduce&lt;Power&gt;");
+        assert!(latex.contains(r"\section*{shallow\_reduce\textless{}Power\textgreater{}}"));
+        assert!(latex.contains(r"\text{shallow\_reduce\textless{}Power\textgreater{}} && x"));
+    }
+}
@@ -0,0 +1,227 @@
+//! Short form of the layout nodes.
+//!
+//! Layouts are the trees displayed by the calculator, e.g. while an expression is being typed,
+//! before it's parsed into an expression. Their short form is the text they display, e.g.
+//! `(1+x)/2` for a `FractionLayout`, so that the parsing of layouts can be followed in the logs.
+
+use crate::poincare::{PoincareAttributes, PoincareNode};
+use crate::render::{
+    self, FunctionRenderer, MatrixRenderer, NameRenderer, NodeRenderer, Operand, Precedence,
+    RenderRegistry, TemplateRenderer,
+};
+
+/// Displays the layouts whose children are positioned relatively to each other
+pub enum LayoutRenderer {
+    /// the children one after the other, e.g. `2x+1` for a `HorizontalLayout`
+    Concatenation,
+    /// `numerator/denominator`
+    Fraction,
+    /// `^exponent` or `_subscript`, the base being the previous layout in the concatenation
+    VerticalOffset,
+    /// `sqrt(radicand)` or `root(radicand, index)`
+    NthRoot,
+}
+impl NodeRenderer for LayoutRenderer {
+    fn render(&self, node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        // the children of a fraction, an offset or a root are displayed as blocks, which need
+        // parentheses in text
+        let block = |child: &Operand| {
+            if child.precedence == Precedence::ATOM {
+                child.text.clone()
+            } else {
+                format!("({})", child.text)
+            }
+        };
+        match self {
+            Self::Concatenation => Some(children.iter().map(|child| child.text.as_str()).collect()),
+            Self::Fraction => match children {
+                [numerator, denominator] => {
+                    Some(format!("{}/{}", block(numerator), block(denominator)))
+                }
+                _ => None,
+            },
+            Self::VerticalOffset => match children {
+                [offset] => {
+                    let position = match &node.attributes {
+                        Some(PoincareAttributes::VerticalOffsetLayout { position })
+                            if position == "subscript" =>
+                        {
+                            "_"
+                        }
+                        // most offsets are exponents
+                        _ => "^",
+                    };
+                    Some(format!("{}{}", position, block(offset)))
+                }
+                _ => None,
+            },
+            Self::NthRoot => match children {
+                [radicand] => Some(format!("sqrt({})", radicand.text)),
+                [radicand, index] => Some(format!("root({}, {})", radicand.text, index.text)),
+                _ => None,
+            },
+        }
+    }
+    fn precedence(&self, node: &PoincareNode) -> u8 {
+        match self {
+            // a concatenation of a single layout is displayed as this layout
+            Self::Concatenation if node.children.len() == 1 => {
+                render::registry().precedence(&node.children[0])
+            }
+            // a number can't be split
+            Self::Concatenation
+                if node.children.iter().all(|child| {
+                    matches!(
+                        &child.attributes,
+                        Some(PoincareAttributes::CodePointLayout { code_point })
+                            if code_point.chars().all(|c| c.is_ascii_digit())
+                    )
+                }) =>
+            {
+                Precedence::ATOM
+            }
+            Self::Concatenation | Self::Fraction => Precedence::MULTIPLICATIVE,
+            Self::VerticalOffset => Precedence::POSTFIX,
+            Self::NthRoot => Precedence::ATOM,
+        }
+    }
+}
+
+/// adds the renderers of the known layouts to `registry`
+pub fn register_builtin(registry: &mut RenderRegistry) {
+    registry.register("HorizontalLayout", LayoutRenderer::Concatenation);
+    registry.register("FractionLayout", LayoutRenderer::Fraction);
+    registry.register("VerticalOffsetLayout", LayoutRenderer::VerticalOffset);
+    registry.register("NthRootLayout", LayoutRenderer::NthRoot);
+    registry.register("MatrixLayout", MatrixRenderer);
+    for (name, plain_name) in [
+        ("EmptyLayout", "\u{25a1}"),
+        ("LeftParenthesisLayout", "("),
+        ("RightParenthesisLayout", ")"),
+        ("LeftSquareBracketLayout", "["),
+        ("RightSquareBracketLayout", "]"),
+        ("LeftCurlyBraceLayout", "{"),
+        ("RightCurlyBraceLayout", "}"),
+    ] {
+        registry.register(name, NameRenderer::new(plain_name));
+    }
+    for (name, function) in [
+        ("BinomialCoefficientLayout", "binomial"),
+        ("ConjugateLayout", "conj"),
+        ("IntegralLayout", "int"),
+        ("ProductLayout", "product"),
+        ("SumLayout", "sum"),
+    ] {
+        registry.register(name, FunctionRenderer::new(function));
+    }
+    for (name, template) in [
+        ("ParenthesisLayout", "({0})"),
+        ("CurlyBraceLayout", "{{{0}}}"),
+        ("AbsoluteValueLayout", "|{0}|"),
+        ("VectorNormLayout", "||{0}||"),
+        ("CeilingLayout", "\u{2308}{0}\u{2309}"),
+        ("FloorLayout", "\u{230a}{0}\u{230b}"),
+        ("CondensedSumLayout", "{0}_{1}^{2}"),
+    ] {
+        registry.register(
+            name,
+            TemplateRenderer::new(template, Precedence::ATOM).unwrap(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log;
+    use crate::poincare::strip_colors;
+
+    /// short form of the layout written in XML as `xml`
+    fn short_form(xml: &str) -> String {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            xml
+        );
+        let node = parse_log(&log).unwrap().remove(0).before.unwrap();
+        strip_colors(&node.pretty_print(0, false).to_string())
+    }
+
+    /// a `HorizontalLayout` of the code points of `text`
+    fn text(text: &str) -> String {
+        let code_points: String = text
+            .chars()
+            .map(|c| {
+                format!(
+                    r#"<CodePointLayout id="c" CodePoint="{}"></CodePointLayout>"#,
+                    c
+                )
+            })
+            .collect();
+        layout("HorizontalLayout", &[code_points])
+    }
+
+    fn layout(name: &str, children: &[String]) -> String {
+        format!(r#"<{0} id="{0}">{1}</{0}>"#, name, children.concat())
+    }
+
+    fn offset(position: &str, children: &[String]) -> String {
+        format!(
+            r#"<VerticalOffsetLayout id="v" position="{}">{}</VerticalOffsetLayout>"#,
+            position,
+            children.concat()
+        )
+    }
+
+    #[test]
+    fn concatenates_code_points() {
+        assert_eq!(short_form(&text("2x+1")), "2x+1");
+    }
+
+    #[test]
+    fn renders_fractions() {
+        let fraction = layout("FractionLayout", &[text("1+x"), text("2")]);
+        assert_eq!(short_form(&fraction), "(1+x)/2");
+        let nested = layout("FractionLayout", &[text("1"), fraction]);
+        assert_eq!(short_form(&nested), "1/((1+x)/2)");
+    }
+
+    #[test]
+    fn renders_superscripts_and_subscripts() {
+        let square = [text("x"), offset("superscript", &[text("2")])];
+        assert_eq!(short_form(&layout("HorizontalLayout", &square)), "x^2");
+        let index = [text("u"), offset("subscript", &[text("n+1")])];
+        assert_eq!(short_form(&layout("HorizontalLayout", &index)), "u_(n+1)");
+    }
+
+    #[test]
+    fn parenthesizes_a_fraction_alone_in_an_offset() {
+        let fraction = layout("FractionLayout", &[text("a"), text("b")]);
+        let exponent = layout("HorizontalLayout", &[fraction]);
+        assert_eq!(short_form(&offset("superscript", &[exponent])), "^(a/b)");
+    }
+
+    #[test]
+    fn renders_roots() {
+        assert_eq!(
+            short_form(&layout("NthRootLayout", &[text("x+1")])),
+            "sqrt(x+1)"
+        );
+        assert_eq!(
+            short_form(&layout("NthRootLayout", &[text("x"), text("3")])),
+            "root(x, 3)"
+        );
+    }
+
+    #[test]
+    fn renders_parentheses() {
+        assert_eq!(
+            short_form(&layout("ParenthesisLayout", &[text("a+b")])),
+            "(a+b)"
+        );
+        let brackets = [
+            layout("LeftParenthesisLayout", &[]),
+            text("a"),
+            layout("RightParenthesisLayout", &[]),
+        ];
+        assert_eq!(short_form(&layout("HorizontalLayout", &brackets)), "(a)");
+    }
+}
@@ -0,0 +1,49 @@
+//! Parsing of the XML logs produced by Poincare.
+//!
+//! The entry point is [`parse_log`], which turns the content of a log into the list of its
+//! top-level [`StepNode`]s. [`StepReader`] reads the steps one by one from any buffered source,
+//! and can skip broken steps.
+
+pub mod diff;
+pub mod dot;
+pub mod error;
+pub mod filter;
+pub mod html;
+pub mod json;
+pub mod latex;
+pub mod layout;
+pub mod output;
+pub mod pattern;
+pub mod picture;
+pub mod poincare;
+pub mod reader;
+pub mod reduction;
+pub mod render;
+pub mod step_filter;
+pub mod theme;
+pub mod track;
+pub mod tui;
+
+pub use diff::{Change, TreeDiff};
+pub use dot::{DotGraph, DotOutput};
+pub use error::{Location, ParseError, ParseErrorKind};
+pub use filter::{MatchFilter, NameFilter, NamePattern, TouchFilter};
+pub use html::HtmlOutput;
+pub use json::JsonOutput;
+pub use latex::LatexOutput;
+pub use output::{Format, Output, TextOutput};
+pub use pattern::Pattern;
+pub use picture::Picture;
+pub use poincare::{CompareMode, PoincareAttributes, PoincareNode};
+pub use reader::{EventReader, Follow, StepReader};
+pub use reduction::{FilterAction, StepNode, StepPart, StepView, ViewOptions};
+pub use render::{NodeRenderer, RenderRegistry};
+pub use step_filter::{StepFilter, StepFilters};
+pub use theme::{ColorMode, Theme, ThemeError};
+pub use track::{Sighting, TrackEvent, Tracker};
+pub use tui::Browser;
+
+/// Parses a whole log, returning all its top-level steps.
+pub fn parse_log(xml: &str) -> Result<Vec<StepNode>, ParseError> {
+    StepReader::new(xml.as_bytes()).collect()
+}
@@ -1,96 +1,469 @@
 use colored::*;
 use std::env;
 use std::fmt::Debug;
-use std::fs::read_to_string;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::process;
 
-use quick_xml::{
-    events::{BytesStart, Event},
-    reader::Reader,
+use poincare_nodes_view::theme::theme;
+use poincare_nodes_view::{
+    Browser, ColorMode, CompareMode, Follow, Format, HtmlOutput, MatchFilter, NameFilter,
+    NamePattern, Output, Pattern, RenderRegistry, StepFilters, StepPart, StepReader, Theme,
+    TouchFilter, Tracker, ViewOptions,
 };
 
-mod poincare;
-mod reduction;
-
-use reduction::{StepNode, StepPart, StepTypeMask};
+/// prints a message about the reading of the logs, on the standard error output when the steps
+/// are written in another format than text
+macro_rules! report {
+    ($arguments:expr, $($message:tt)*) => {
+        if $arguments.format == Format::Text {
+            println!($($message)*)
+        } else {
+            eprintln!($($message)*)
+        }
+    };
+}
 
 fn main() {
     let mut arguments = Arguments::from_args(env::args());
-    if arguments.files.len() == 0 {
+    if arguments.files.is_empty() {
         arguments.files.push(String::from("poincare-log.xml"));
     }
-
-    for file in &arguments.files {
-        let start_file_str = format!("Reading file `{}`", file);
-        println!("{}", start_file_str.red());
-        let xml_string_result = read_to_string(file);
-        let xml_string = match xml_string_result {
-            Err(e) => {
+    if arguments.tui && arguments.follow {
+        // the steps are browsed once they have all been read
+        eprintln!("'--follow' can't be used with '--tui', ignoring it");
+        arguments.follow = false;
+    }
+    if arguments.format != Format::Text && (arguments.tui || arguments.track.is_some()) {
+        eprintln!("'--format' can't be used with '--tui' or '--track', ignoring it");
+        arguments.format = Format::Text;
+    }
+    colored::control::set_override(arguments.color.enabled());
+    if let Some(name) = &arguments.theme {
+        let theme = match Theme::from_name(name) {
+            Some(theme) => theme,
+            None => match Theme::load(Path::new(name)) {
+                Ok(theme) => theme,
+                Err(e) => {
+                    let error_str = format!("{}", e);
+                    report!(
+                        arguments,
+                        "Error while reading theme `{}`: {}",
+                        name,
+                        error_str.color(theme().error)
+                    );
+                    process::exit(1);
+                }
+            },
+        };
+        // nothing has been displayed yet, so the default theme isn't in use
+        let _ = theme.install();
+    }
+    if !arguments.rules_files.is_empty() {
+        let mut registry = RenderRegistry::builtin();
+        for file in &arguments.rules_files {
+            if let Err(e) = registry.load_rules(Path::new(file)) {
                 let error_str = format!("{}", e);
-                println!("Error while opening `{}`: {}", file, error_str.red());
-                return;
+                report!(
+                    arguments,
+                    "Error while reading rules `{}`: {}",
+                    file,
+                    error_str.color(theme().error)
+                );
+                process::exit(1);
+            }
+        }
+        // nothing has been displayed yet, so the default registry isn't in use
+        let _ = registry.install();
+    }
+    let mut step_filters = StepFilters::builtin(arguments.compare_mode);
+    for (name, enabled) in &arguments.filter_switches {
+        if let Err(e) = step_filters.set_enabled(name, *enabled) {
+            report!(
+                arguments,
+                "Error in the filters: {}",
+                e.color(theme().error)
+            );
+            process::exit(1);
+        }
+    }
+    if arguments.list_filters {
+        println!("Filters hiding the steps which aren't interesting:");
+        for (filter, enabled) in step_filters.iter() {
+            let state_str = if enabled { "enabled" } else { "disabled" };
+            println!(
+                "  {} ({}): {}",
+                filter.name().color(theme().step).bold(),
+                state_str,
+                filter.description()
+            );
+        }
+        return;
+    }
+    let mut fold = Vec::new();
+    for pattern in &arguments.fold {
+        match NamePattern::new(pattern) {
+            Err(e) => {
+                report!(
+                    arguments,
+                    "Error in the step name pattern `{}`: {}",
+                    pattern,
+                    e.color(theme().error)
+                );
+                process::exit(1);
+            }
+            Ok(name_pattern) => fold.push(name_pattern),
+        }
+    }
+    let view_options = ViewOptions {
+        long_form: arguments.print_long_form,
+        matrix_box: arguments.matrix_box,
+        diff: arguments.diff,
+        max_depth: arguments.max_depth,
+        fold,
+        two_d: arguments.two_d,
+    };
+    let mut tracker = arguments.track.as_deref().map(Tracker::new);
+    let mut name_filter = NameFilter::new().collapse(arguments.collapse);
+    let mut touch_filter = TouchFilter::new().collapse(arguments.collapse);
+    for name in &arguments.touching {
+        touch_filter = touch_filter.name(name);
+    }
+    for (pattern, hide) in arguments.name_patterns.iter() {
+        let name_pattern = match NamePattern::new(pattern) {
+            Err(e) => {
+                report!(
+                    arguments,
+                    "Error in the step name pattern `{}`: {}",
+                    pattern,
+                    e.color(theme().error)
+                );
+                process::exit(1);
+            }
+            Ok(name_pattern) => name_pattern,
+        };
+        name_filter = if *hide {
+            name_filter.hide(name_pattern)
+        } else {
+            name_filter.only(name_pattern)
+        };
+    }
+    let mut match_filter = MatchFilter::new().collapse(arguments.collapse);
+    for (pattern, after) in &arguments.expression_patterns {
+        let expression_pattern = match Pattern::parse(pattern) {
+            Err(e) => {
+                report!(
+                    arguments,
+                    "Error in the expression pattern `{}`: {}",
+                    pattern,
+                    e.color(theme().error)
+                );
+                process::exit(1);
             }
-            Ok(xml_string) => xml_string,
+            Ok(expression_pattern) => expression_pattern,
+        };
+        match_filter = if *after {
+            match_filter.after(expression_pattern)
+        } else {
+            match_filter.before(expression_pattern)
         };
-        let mut reader = Reader::from_str(&xml_string);
-        reader.trim_text(true);
-        loop {
-            match reader.read_event() {
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                Ok(Event::Eof) => break,
-                Ok(Event::Start(start)) => match start.name().as_ref() {
-                    b"Step" => {
-                        let mut step = StepNode::from_start(&start);
-                        step.build(&mut reader);
-                        if !arguments.show_useless {
-                            let steps_to_remove_mask = StepTypeMask {
-                                based_integer_to_rational: !arguments.show_number_to_rational,
-                                to_undef: !arguments.show_to_undef,
-                            };
+    }
+    // the steps kept for the interactive browser
+    let mut browsed_steps = Vec::new();
+    let output = match arguments.format {
+        // the colors of the terminal are turned into CSS classes
+        Format::Html => HtmlOutput::new(io::stdout(), &view_options).map(|output| {
+            Box::new(output.colors(arguments.color != ColorMode::Never)) as Box<dyn Output>
+        }),
+        format => format.output(io::stdout(), &view_options),
+    };
+    let mut output = match output {
+        Err(e) => {
+            let error_str = format!("{}", e);
+            report!(
+                arguments,
+                "Error while writing the steps: {}",
+                error_str.color(theme().error)
+            );
+            process::exit(1);
+        }
+        Ok(output) => output,
+    };
 
-                            StepPart::remove_useless_recursive(
-                                &mut step.parts,
-                                |part| match part {
-                                    StepPart::State(..) => arguments.dont_show_intermediate_states,
-                                    StepPart::Substep(step) => {
-                                        step.does_nothing()
-                                            || steps_to_remove_mask.step_is_either(step)
-                                    }
-                                },
-                            );
-                        }
-                        println!("{}\n", step.view(arguments.print_long_form));
+    // a broken log doesn't stop the next ones from being read, and the output is still finished
+    // so that it stays well-formed
+    let mut failed = false;
+    for (file_n, file) in arguments.files.iter().enumerate() {
+        let source: Box<dyn BufRead> = if file == "-" {
+            report!(
+                arguments,
+                "{}",
+                "Reading standard input".color(theme().error)
+            );
+            Box::new(io::stdin().lock())
+        } else {
+            let start_file_str = format!("Reading file `{}`", file);
+            report!(arguments, "{}", start_file_str.color(theme().error));
+            let xml_file = match File::open(file) {
+                Err(e) => {
+                    let error_str = format!("{}", e);
+                    report!(
+                        arguments,
+                        "Error while opening `{}`: {}",
+                        file,
+                        error_str.color(theme().error)
+                    );
+                    failed = true;
+                    continue;
+                }
+                Ok(xml_file) => xml_file,
+            };
+            if arguments.follows(file_n) {
+                Box::new(BufReader::new(Follow::new(xml_file)))
+            } else {
+                Box::new(BufReader::new(xml_file))
+            }
+        };
+        // the steps are read and displayed one by one, so the file is never fully in memory
+        let mut step_reader = StepReader::new(source).recover(arguments.recover);
+        for step in &mut step_reader {
+            let step = match step {
+                Err(e) => {
+                    let error_str = format!("{}", e);
+                    report!(
+                        arguments,
+                        "Error while parsing `{}`: {}",
+                        file,
+                        error_str.color(theme().error)
+                    );
+                    failed = true;
+                    break;
+                }
+                Ok(step) => step,
+            };
+            if let Some(tracker) = &mut tracker {
+                // the steps themselves aren't displayed when tracking a node
+                for sighting in tracker.track(&step) {
+                    println!("{}", sighting.pretty_print(arguments.print_long_form));
+                }
+                continue;
+            }
+            let steps =
+                if name_filter.is_empty() && touch_filter.is_empty() && match_filter.is_empty() {
+                    vec![step]
+                } else {
+                    // the top-level step itself may be dropped or collapsed
+                    let name = step.name.clone();
+                    let mut parts = vec![StepPart::Substep(step)];
+                    if !name_filter.is_empty() {
+                        StepPart::filter_recursive(&mut parts, |step| name_filter.action(step));
                     }
-                    string => panic_event(&reader, String::from_utf8(string.to_vec()).unwrap()),
-                },
-                Ok(ev) => panic_event(&reader, ev),
+                    if !touch_filter.is_empty() {
+                        StepPart::filter_recursive(&mut parts, |step| touch_filter.action(step));
+                    }
+                    if !match_filter.is_empty() {
+                        StepPart::filter_recursive(&mut parts, |step| match_filter.action(step));
+                    }
+                    StepPart::into_steps(parts, &name)
+                };
+            for mut step in steps {
+                if !arguments.show_useless {
+                    StepPart::remove_useless_recursive(&mut step.parts, |part| match part {
+                        StepPart::State(..) => arguments.dont_show_intermediate_states,
+                        StepPart::Substep(step) => step_filters.hides(step),
+                    });
+                }
+                if arguments.tui {
+                    browsed_steps.push(step);
+                } else if let Err(e) = output.write_step(&step) {
+                    let error_str = format!("{}", e);
+                    report!(
+                        arguments,
+                        "Error while writing the steps: {}",
+                        error_str.color(theme().error)
+                    );
+                    process::exit(1);
+                }
+            }
+        }
+        let skipped = step_reader.skipped();
+        if !skipped.is_empty() {
+            let skipped_str = format!("Skipped {} broken step(s) in `{}`:", skipped.len(), file);
+            report!(arguments, "{}", skipped_str.color(theme().error));
+            for error in skipped {
+                report!(arguments, "  {}", error);
             }
         }
     } // for each file
+    if let Err(e) = output.finish() {
+        let error_str = format!("{}", e);
+        report!(
+            arguments,
+            "Error while writing the steps: {}",
+            error_str.color(theme().error)
+        );
+        process::exit(1);
+    }
+    if failed {
+        process::exit(1);
+    }
+    if let (Some(tracker), Some(id)) = (&tracker, &arguments.track) {
+        if tracker.sightings() == 0 {
+            let not_found_str = format!("Node `{}` isn't in the log", id);
+            println!("{}", not_found_str.color(theme().error));
+        }
+    } else if arguments.tui {
+        if let Err(e) = Browser::new(&browsed_steps, &view_options).run() {
+            let error_str = format!("{}", e);
+            report!(
+                arguments,
+                "Error in the terminal: {}",
+                error_str.color(theme().error)
+            );
+            process::exit(1);
+        }
+    }
 }
 
 /// display options read from the command line
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 struct Arguments {
     show_useless: bool,
-    show_number_to_rational: bool,
-    show_to_undef: bool,
+    // filters of the uninteresting steps to enable, or to disable if the flag is false
+    filter_switches: Vec<(String, bool)>,
+    list_filters: bool,
     dont_show_intermediate_states: bool,
     print_long_form: bool,
-    // list of files to analyse
+    matrix_box: bool,
+    // draw the expressions on several lines
+    two_d: bool,
+    diff: bool,
+    // what must be the same before and after a step for it to do nothing
+    compare_mode: CompareMode,
+    // id of the node whose life story is displayed instead of the steps
+    track: Option<String>,
+    // patterns of the step names to display, or to hide if the flag is true
+    name_patterns: Vec<(String, bool)>,
+    // names of the nodes which must be touched by the displayed steps
+    touching: Vec<String>,
+    // patterns of the expressions before the displayed steps, or after them if the flag is true
+    expression_patterns: Vec<(String, bool)>,
+    collapse: bool,
+    // number of steps around which the steps are summarized
+    max_depth: Option<usize>,
+    // patterns of the names of the steps displayed without their content
+    fold: Vec<String>,
+    recover: bool,
+    follow: bool,
+    // browse the steps interactively instead of printing them
+    tui: bool,
+    // format in which the steps are written on the standard output
+    format: Format,
+    color: ColorMode,
+    // name of a built-in theme, or file of a theme
+    theme: Option<String>,
+    // files with rules to display more nodes in the short form
+    rules_files: Vec<String>,
+    // list of files to analyse, `-` being the standard input
     files: Vec<String>,
 }
 impl Arguments {
-    fn from_args(args: env::Args) -> Self {
+    fn from_args(args: impl Iterator<Item = String>) -> Self {
         let mut arguments = Self::default();
         // the first argument is almost always the program name or the path it was run from
-        for arg in args.skip(1) {
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
             let arg = arg.trim();
             match arg {
+                "--rules" => match args.next() {
+                    Some(file_name) => arguments.rules_files.push(file_name),
+                    None => eprintln!("Missing file name after '--rules', skipping"),
+                },
+                "--compare" => match args.next().as_deref().map(CompareMode::from_name) {
+                    Some(Some(mode)) => arguments.compare_mode = mode,
+                    Some(None) => eprintln!("Unknown comparison mode after '--compare', skipping"),
+                    None => eprintln!("Missing comparison mode after '--compare', skipping"),
+                },
+                "--track" => match args.next() {
+                    Some(id) => arguments.track = Some(id),
+                    None => eprintln!("Missing node id after '--track', skipping"),
+                },
+                "--only" => match args.next() {
+                    Some(pattern) => arguments.name_patterns.push((pattern, false)),
+                    None => eprintln!("Missing pattern after '--only', skipping"),
+                },
+                "--hide" => match args.next() {
+                    Some(pattern) => arguments.name_patterns.push((pattern, true)),
+                    None => eprintln!("Missing pattern after '--hide', skipping"),
+                },
+                "--touching" => match args.next() {
+                    Some(names) => arguments
+                        .touching
+                        .extend(names.split(',').map(|name| String::from(name.trim()))),
+                    None => eprintln!("Missing node names after '--touching', skipping"),
+                },
+                "--match-before" | "--match-after" => match args.next() {
+                    Some(pattern) => arguments
+                        .expression_patterns
+                        .push((pattern, arg == "--match-after")),
+                    None => eprintln!("Missing pattern after '{}', skipping", arg),
+                },
+                "--collapse" => arguments.collapse = true,
+                "--max-depth" => match args.next().map(|depth| depth.parse()) {
+                    Some(Ok(depth)) => arguments.max_depth = Some(depth),
+                    Some(Err(_)) => eprintln!("Invalid depth after '--max-depth', skipping"),
+                    None => eprintln!("Missing depth after '--max-depth', skipping"),
+                },
+                "--fold" => match args.next() {
+                    Some(pattern) => arguments.fold.push(pattern),
+                    None => eprintln!("Missing pattern after '--fold', skipping"),
+                },
                 "--useless" => arguments.show_useless = true,
-                "--number-to-rational" => arguments.show_number_to_rational = true,
-                "--to-undef" => arguments.show_to_undef = true,
+                "--enable-filter" | "--disable-filter" => match args.next() {
+                    Some(names) => arguments.filter_switches.extend(
+                        names
+                            .split(',')
+                            .map(|name| (String::from(name.trim()), arg == "--enable-filter")),
+                    ),
+                    None => eprintln!("Missing filter names after '{}', skipping", arg),
+                },
+                "--list-filters" => arguments.list_filters = true,
+                // aliases of older versions
+                "--number-to-rational" => arguments
+                    .filter_switches
+                    .push((String::from("number-to-rational"), false)),
+                "--to-undef" => arguments
+                    .filter_switches
+                    .push((String::from("to-undef"), false)),
                 "--long" => arguments.print_long_form = true,
+                "--matrix-box" => arguments.matrix_box = true,
+                "--2d" => arguments.two_d = true,
+                "--diff" => arguments.diff = true,
                 "--no-states" => arguments.dont_show_intermediate_states = true,
+                "--recover" => arguments.recover = true,
+                "--follow" => arguments.follow = true,
+                "--tui" => arguments.tui = true,
+                "--format" => match args.next().as_deref().map(Format::from_name) {
+                    Some(Some(format)) => arguments.format = format,
+                    Some(None) => eprintln!("Unknown format after '--format', skipping"),
+                    None => eprintln!("Missing format after '--format', skipping"),
+                },
+                "--color" => match args.next().as_deref().map(ColorMode::from_name) {
+                    Some(Some(mode)) => arguments.color = mode,
+                    Some(None) => eprintln!("Unknown color mode after '--color', skipping"),
+                    None => eprintln!("Missing color mode after '--color', skipping"),
+                },
+                option if option.starts_with("--color=") => {
+                    match ColorMode::from_name(&option["--color=".len()..]) {
+                        Some(mode) => arguments.color = mode,
+                        None => eprintln!("Unknown color mode in '{}', skipping", option),
+                    }
+                }
+                "--theme" => match args.next() {
+                    Some(theme) => arguments.theme = Some(theme),
+                    None => eprintln!("Missing theme after '--theme', skipping"),
+                },
                 file_name if !file_name.starts_with("--") => {
                     arguments.files.push(String::from(file_name))
                 }
@@ -99,36 +472,44 @@ impl Arguments {
         }
         arguments
     }
-}
-impl Default for Arguments {
-    fn default() -> Self {
-        Self {
-            show_useless: false,
-            show_number_to_rational: false,
-            show_to_undef: false,
-            dont_show_intermediate_states: false,
-            print_long_form: false,
-            files: Vec::new(),
-        }
+    /// whether the file number `file_n` is followed, which only the last file can be as
+    /// following never ends
+    fn follows(&self, file_n: usize) -> bool {
+        self.follow && file_n == self.files.len() - 1
     }
 }
 
-fn panic_event<T: Debug>(reader: &Reader<&[u8]>, event: T) -> ! {
-    panic!(
-        "Unexpected `{:?}` at position {}",
-        event,
-        reader.buffer_position()
-    );
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn get_attribute_from_start(start: &BytesStart, attr_name: &[u8]) -> Option<String> {
-    let mut value: Option<String> = None;
-    for attr in start.attributes() {
-        let attr = attr.unwrap();
-        if attr.key.as_ref() == attr_name {
-            value = Some(String::from_utf8(attr.value.to_vec()).unwrap());
-            break;
-        }
+    fn arguments(args: &[&str]) -> Arguments {
+        let args = ["poincare-nodes-view"].iter().chain(args);
+        Arguments::from_args(args.map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn reads_the_standard_input_as_a_file() {
+        assert_eq!(arguments(&["a.xml", "-"]).files, ["a.xml", "-"]);
+    }
+
+    #[test]
+    fn follows_only_the_last_file() {
+        let arguments = arguments(&["--follow", "a.xml", "-"]);
+        assert!(!arguments.follows(0));
+        assert!(arguments.follows(1));
+    }
+
+    #[test]
+    fn follows_no_file_by_default() {
+        assert!(!arguments(&["a.xml"]).follows(0));
+    }
+
+    #[test]
+    fn reads_the_compare_mode() {
+        assert_eq!(arguments(&[]).compare_mode, CompareMode::Full);
+        let arguments = arguments(&["--compare", "identity", "a.xml"]);
+        assert_eq!(arguments.compare_mode, CompareMode::Identity);
+        assert_eq!(arguments.files, ["a.xml"]);
     }
-    value
 }
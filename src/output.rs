@@ -0,0 +1,90 @@
+//! Formats in which the steps are written, e.g. to share them outside of the terminal.
+//!
+//! Each format is an [`Output`], which writes the top-level steps one by one as they're read.
+
+use std::io::{self, Write};
+
+use crate::dot::{DotGraph, DotOutput};
+use crate::html::HtmlOutput;
+use crate::json::JsonOutput;
+use crate::latex::LatexOutput;
+use crate::reduction::{StepNode, ViewOptions};
+
+/// A format in which the steps are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// colored text, as displayed in the terminal
+    #[default]
+    Text,
+    /// a self-contained HTML page, where the steps can be expanded and collapsed
+    Html,
+    /// a JSON document with all the steps, see [`crate::json`]
+    Json,
+    /// JSON Lines, with a top-level step per line
+    JsonLines,
+    /// Graphviz graphs of the expressions before and after each step
+    Dot,
+    /// Graphviz graphs of the steps called by each top-level step
+    DotSteps,
+    /// a LaTeX document with the expressions before and after each step as equations
+    Latex,
+}
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "text" => Self::Text,
+            "html" => Self::Html,
+            "json" => Self::Json,
+            "jsonl" => Self::JsonLines,
+            "dot" => Self::Dot,
+            "dot-steps" => Self::DotSteps,
+            "latex" => Self::Latex,
+            _ => return None,
+        })
+    }
+    /// an output writing the steps to `writer` in this format, displayed according to `options`
+    pub fn output<'a, W: Write + 'a>(
+        self,
+        writer: W,
+        options: &'a ViewOptions,
+    ) -> io::Result<Box<dyn Output + 'a>> {
+        Ok(match self {
+            Self::Text => Box::new(TextOutput::new(writer, options)),
+            Self::Html => Box::new(HtmlOutput::new(writer, options)?),
+            Self::Json => Box::new(JsonOutput::new(writer, false)?),
+            Self::JsonLines => Box::new(JsonOutput::new(writer, true)?),
+            Self::Dot => Box::new(DotOutput::new(writer, DotGraph::Expressions)),
+            Self::DotSteps => Box::new(DotOutput::new(writer, DotGraph::Steps)),
+            Self::Latex => Box::new(LatexOutput::new(writer)?),
+        })
+    }
+}
+
+/// Writes the top-level steps in a format
+pub trait Output {
+    /// writes a top-level step, after the previous ones
+    fn write_step(&mut self, step: &StepNode) -> io::Result<()>;
+    /// writes what comes after the last step
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the steps as they're displayed in the terminal, see [`StepNode::view`]
+pub struct TextOutput<'a, W: Write> {
+    writer: W,
+    options: &'a ViewOptions,
+}
+impl<'a, W: Write> TextOutput<'a, W> {
+    pub fn new(writer: W, options: &'a ViewOptions) -> Self {
+        Self { writer, options }
+    }
+}
+impl<'a, W: Write> Output for TextOutput<'a, W> {
+    fn write_step(&mut self, step: &StepNode) -> io::Result<()> {
+        writeln!(self.writer, "{}\n", step.view(self.options))
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
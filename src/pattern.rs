@@ -0,0 +1,324 @@
+//! Patterns matching Poincare expressions, e.g. `Power(Rational, Rational[denominator=1])`.
+//!
+//! The syntax of a pattern is:
+//! * `Name` matches a node called `Name`, whatever its children, and `A|B` a node called `A` or
+//!   `B`. `_` matches any node
+//! * `Name[attribute=value, other!=value]` matches a node whose attributes, as written in the
+//!   log, have or don't have the given values
+//! * `Name(p, q)` matches a node with exactly two children, matching `p` and `q`. `*` matches any
+//!   number of children, e.g. `Addition(*, Power, *)` matches an addition with a `Power` child
+//! * `..p` matches a node if it, or any node inside it, matches `p`
+
+use crate::poincare::PoincareNode;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AttributePredicate {
+    name: String,
+    value: String,
+    equal: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChildPattern {
+    One(NodePattern),
+    /// any number of children
+    Any,
+}
+
+/// A pattern matching Poincare nodes, see the [module documentation](self) for its syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    root: NodePattern,
+}
+impl Pattern {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            text,
+            chars: text.char_indices().peekable(),
+        };
+        let root = parser.pattern()?;
+        parser.skip_spaces();
+        match parser.chars.next() {
+            None => Ok(Self { root }),
+            Some((position, c)) => Err(parser.error(position, &format!("unexpected `{}`", c))),
+        }
+    }
+    pub fn matches(&self, node: &PoincareNode) -> bool {
+        self.root.matches(node)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NodePattern {
+    Node {
+        /// names the node may have, any name if `None`
+        names: Option<Vec<String>>,
+        attributes: Vec<AttributePredicate>,
+        /// patterns of the children, any children if `None`
+        children: Option<Vec<ChildPattern>>,
+    },
+    /// the node or one of its descendants matches the pattern
+    Descendant(Box<NodePattern>),
+}
+impl NodePattern {
+    fn matches(&self, node: &PoincareNode) -> bool {
+        match self {
+            Self::Descendant(pattern) => {
+                pattern.matches(node) || node.children.iter().any(|child| self.matches(child))
+            }
+            Self::Node {
+                names,
+                attributes,
+                children,
+            } => {
+                names
+                    .as_ref()
+                    .is_none_or(|names| names.contains(&node.name))
+                    && attributes.iter().all(|predicate| {
+                        let value = node
+                            .attributes
+                            .as_ref()
+                            .and_then(|attributes| attributes.get(&predicate.name));
+                        (value == Some(predicate.value.as_str())) == predicate.equal
+                    })
+                    && children
+                        .as_ref()
+                        .is_none_or(|patterns| match_children(patterns, &node.children))
+            }
+        }
+    }
+}
+
+fn match_children(patterns: &[ChildPattern], children: &[PoincareNode]) -> bool {
+    match patterns.split_first() {
+        None => children.is_empty(),
+        Some((ChildPattern::Any, patterns)) => {
+            (0..=children.len()).any(|skipped| match_children(patterns, &children[skipped..]))
+        }
+        Some((ChildPattern::One(pattern), patterns)) => match children.split_first() {
+            Some((child, children)) => pattern.matches(child) && match_children(patterns, children),
+            None => false,
+        },
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+impl<'a> Parser<'a> {
+    fn error(&self, position: usize, message: &str) -> String {
+        format!("{} at column {} of `{}`", message, position + 1, self.text)
+    }
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+    /// consumes `expected` if it's the next character, ignoring spaces
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_spaces();
+        self.chars.next_if(|(_, c)| *c == expected).is_some()
+    }
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        Err(match self.chars.peek().copied() {
+            Some((position, c)) => {
+                self.error(position, &format!("expected `{}`, found `{}`", expected, c))
+            }
+            None => self.error(self.text.len(), &format!("expected `{}`", expected)),
+        })
+    }
+    /// a name, or the value of an attribute if `value` is true
+    fn word(&mut self, value: bool) -> Result<String, String> {
+        self.skip_spaces();
+        let position = self
+            .chars
+            .peek()
+            .map_or(self.text.len(), |(position, _)| *position);
+        if value && self.eat('"') {
+            let mut word = String::new();
+            loop {
+                match self.chars.next() {
+                    Some((_, '"')) => return Ok(word),
+                    Some((_, c)) => word.push(c),
+                    None => return Err(self.error(self.text.len(), "unclosed `\"`")),
+                }
+            }
+        }
+        let mut word = String::new();
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| {
+            c.is_alphanumeric() || *c == '_' || *c == ':' || (value && "-+./".contains(*c))
+        }) {
+            word.push(c);
+        }
+        if word.is_empty() {
+            return Err(self.error(
+                position,
+                if value {
+                    "expected a value"
+                } else {
+                    "expected a name"
+                },
+            ));
+        }
+        Ok(word)
+    }
+    fn pattern(&mut self) -> Result<NodePattern, String> {
+        self.skip_spaces();
+        if self.eat('.') {
+            self.expect('.')?;
+            return Ok(NodePattern::Descendant(Box::new(self.pattern()?)));
+        }
+        let mut names = vec![self.word(false)?];
+        while self.eat('|') {
+            names.push(self.word(false)?);
+        }
+        let names = if names == ["_"] { None } else { Some(names) };
+        let mut attributes = Vec::new();
+        if self.eat('[') {
+            loop {
+                let name = self.word(false)?;
+                let equal = !self.eat('!');
+                self.expect('=')?;
+                let value = self.word(true)?;
+                attributes.push(AttributePredicate { name, value, equal });
+                if !self.eat(',') {
+                    break;
+                }
+            }
+            self.expect(']')?;
+        }
+        let mut children = None;
+        if self.eat('(') {
+            let mut patterns = Vec::new();
+            if !self.eat(')') {
+                loop {
+                    patterns.push(if self.eat('*') {
+                        ChildPattern::Any
+                    } else {
+                        ChildPattern::One(self.pattern()?)
+                    });
+                    if !self.eat(',') {
+                        break;
+                    }
+                }
+                self.expect(')')?;
+            }
+            children = Some(patterns);
+        }
+        Ok(NodePattern::Node {
+            names,
+            attributes,
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    /// `(x + 1/2) ^ 2`
+    const POWER: &str = r#"<Power id="1">
+  <Addition id="2">
+    <Symbol id="3" name="x"></Symbol>
+    <Rational id="4" negative="0" numerator="1" denominator="2"></Rational>
+  </Addition>
+  <Rational id="5" negative="0" numerator="2" denominator="1"></Rational>
+</Power>"#;
+
+    fn power() -> PoincareNode {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            POWER
+        );
+        parse_log(&log).unwrap().remove(0).before.unwrap()
+    }
+
+    fn matches(pattern: &str) -> bool {
+        Pattern::parse(pattern).unwrap().matches(&power())
+    }
+
+    #[test]
+    fn matches_names() {
+        assert!(matches("Power"));
+        assert!(matches("_"));
+        assert!(matches("Addition|Power"));
+        assert!(!matches("Addition"));
+    }
+
+    #[test]
+    fn matches_attributes() {
+        assert!(matches("Power(_, Rational[denominator=1])"));
+        assert!(matches("Power(_, Rational[numerator=2, denominator=1])"));
+        assert!(matches("Power(_, Rational[negative!=1])"));
+        assert!(matches(r#"Power(Addition(Symbol[name="x"], _), _)"#));
+        assert!(!matches("Power(_, Rational[denominator=2])"));
+        // a missing attribute has none of the values
+        assert!(matches("Power[denominator!=1]"));
+        assert!(!matches("Power[denominator=1]"));
+    }
+
+    #[test]
+    fn matches_the_children() {
+        assert!(matches("Power(Addition, Rational)"));
+        assert!(matches("Power(Addition(Symbol, Rational), Rational)"));
+        assert!(!matches("Power(Addition)"));
+        assert!(!matches("Power(Addition, Rational, _)"));
+        assert!(!matches("Power()"));
+        assert!(matches("Power(*)"));
+        assert!(matches("Power(*, Rational)"));
+        assert!(matches("Power(Addition(*, Rational, *), *)"));
+        assert!(!matches("Power(*, Addition)"));
+    }
+
+    #[test]
+    fn matches_the_descendants() {
+        assert!(matches("..Symbol"));
+        assert!(matches("..Power"));
+        assert!(matches("..Addition(_, Rational[denominator=2])"));
+        assert!(matches("Power(..Rational, _)"));
+        assert!(!matches("..Cosine"));
+        assert!(!matches("Power(_, ..Symbol)"));
+    }
+
+    #[test]
+    fn ignores_spaces() {
+        assert!(matches(
+            " Power ( Addition ( * ) , Rational [ denominator = 1 ] ) "
+        ));
+    }
+
+    fn error(pattern: &str) -> String {
+        Pattern::parse(pattern).unwrap_err()
+    }
+
+    #[test]
+    fn reports_where_a_pattern_is_wrong() {
+        assert_eq!(error(""), "expected a name at column 1 of ``");
+        assert_eq!(error("Power)"), "unexpected `)` at column 6 of `Power)`");
+        assert_eq!(
+            error("Power(_, _"),
+            "expected `)` at column 11 of `Power(_, _`"
+        );
+        assert_eq!(
+            error("Rational[denominator]"),
+            "expected `=`, found `]` at column 21 of `Rational[denominator]`"
+        );
+        assert_eq!(
+            error("Rational[denominator=]"),
+            "expected a value at column 22 of `Rational[denominator=]`"
+        );
+        assert_eq!(
+            error(r#"Symbol[name="x]"#),
+            r#"unclosed `"` at column 16 of `Symbol[name="x]`"#
+        );
+        assert_eq!(
+            error(".Power"),
+            "expected `.`, found `P` at column 2 of `.Power`"
+        );
+        assert_eq!(error("Power|"), "expected a name at column 7 of `Power|`");
+    }
+}
@@ -0,0 +1,579 @@
+//! 2D form of the Poincare nodes, drawn on several lines like on paper: numerators are stacked
+//! over denominators, exponents are raised, radicals are drawn and matrices are boxes, e.g.
+//! ```text
+//!         2
+//!   _    x
+//! ╲╱x + ────
+//!        3
+//! ```
+//!
+//! The other nodes are displayed as in the short form, with parentheses as tall as what they
+//! enclose.
+
+use colored::*;
+
+use crate::poincare::{visible_width, PoincareAttributes, PoincareNode};
+use crate::render::{self, Precedence};
+
+/// starts the placeholder of a child in the short form of its parent, followed by the index of
+/// the child and [`PLACEHOLDER_END`]
+const PLACEHOLDER_START: char = '\u{E010}';
+const PLACEHOLDER_END: char = '\u{E011}';
+
+/// A block of lines, placed next to other blocks so that their baselines are on the same line
+#[derive(Debug, Clone)]
+pub struct Picture {
+    /// the lines, which all display `width` characters
+    pub lines: Vec<String>,
+    /// index of the line aligned with the baselines of the blocks next to it, e.g. the line of
+    /// the bar of a fraction
+    pub baseline: usize,
+    width: usize,
+}
+impl Picture {
+    fn text(text: String) -> Self {
+        Self {
+            width: visible_width(&text),
+            lines: vec![text],
+            baseline: 0,
+        }
+    }
+    fn blank_line(&self) -> String {
+        " ".repeat(self.width)
+    }
+    /// the picture with `top` blank lines above it and `bottom` blank lines below it
+    fn padded(mut self, top: usize, bottom: usize) -> Self {
+        let blank_line = self.blank_line();
+        self.lines
+            .splice(0..0, std::iter::repeat_n(blank_line.clone(), top));
+        self.lines.extend(std::iter::repeat_n(blank_line, bottom));
+        self.baseline += top;
+        self
+    }
+    /// the pictures next to each other, from left to right
+    fn beside(pictures: Vec<Self>) -> Self {
+        let above = pictures
+            .iter()
+            .map(|picture| picture.baseline)
+            .max()
+            .unwrap_or(0);
+        let below = pictures
+            .iter()
+            .map(|picture| picture.lines.len() - picture.baseline - 1)
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![String::new(); above + below + 1];
+        for picture in &pictures {
+            let top = above - picture.baseline;
+            for (line_n, line) in lines.iter_mut().enumerate() {
+                match line_n
+                    .checked_sub(top)
+                    .and_then(|line_n| picture.lines.get(line_n))
+                {
+                    Some(picture_line) => line.push_str(picture_line),
+                    None => line.push_str(&picture.blank_line()),
+                }
+            }
+        }
+        Self {
+            lines,
+            baseline: above,
+            width: pictures.iter().map(|picture| picture.width).sum(),
+        }
+    }
+    /// the numerator over the denominator, separated by a bar on the baseline
+    fn fraction(numerator: Self, denominator: Self, color: Color) -> Self {
+        let width = numerator.width.max(denominator.width) + 2;
+        let centered = |picture: Self| {
+            let left = (width - picture.width) / 2;
+            let right = width - picture.width - left;
+            picture
+                .lines
+                .into_iter()
+                .map(move |line| format!("{}{}{}", " ".repeat(left), line, " ".repeat(right)))
+        };
+        let baseline = numerator.lines.len();
+        let mut lines: Vec<String> = centered(numerator).collect();
+        lines.push("\u{2500}".repeat(width).color(color).to_string());
+        lines.extend(centered(denominator));
+        Self {
+            lines,
+            baseline,
+            width,
+        }
+    }
+    /// the exponent above the right of the base
+    fn power(base: Self, exponent: Self) -> Self {
+        let exponent_height = exponent.lines.len();
+        let base_padding = base.blank_line();
+        let exponent_padding = exponent.blank_line();
+        let mut lines: Vec<String> = exponent
+            .lines
+            .into_iter()
+            .map(|line| format!("{}{}", base_padding, line))
+            .collect();
+        lines.extend(
+            base.lines
+                .into_iter()
+                .map(|line| format!("{}{}", line, exponent_padding)),
+        );
+        Self {
+            lines,
+            baseline: exponent_height + base.baseline,
+            width: base.width + exponent.width,
+        }
+    }
+    /// the radicand under a radical, with the index on its left if there is one, e.g.
+    /// ```text
+    ///    ___
+    /// 3 ╱ 1
+    ///  ╱ ───
+    /// ╲╱  x
+    /// ```
+    fn radical(radicand: Self, index: Option<Self>, color: Color) -> Self {
+        let height = radicand.lines.len();
+        let mut lines = vec![format!(
+            "{}{}",
+            " ".repeat(height + 1),
+            "_".repeat(radicand.width).color(color)
+        )];
+        for (line_n, line) in radicand.lines.into_iter().enumerate() {
+            let row = line_n + 1;
+            let stroke = if row == height {
+                "\u{2572}\u{2571}".color(color)
+            } else {
+                "\u{2571}".color(color)
+            };
+            let indentation = if row == height { 0 } else { height - row + 1 };
+            lines.push(format!(
+                "{}{}{}{}",
+                " ".repeat(indentation),
+                stroke,
+                " ".repeat(row - 1),
+                line
+            ));
+        }
+        let radical = Self {
+            lines,
+            baseline: radicand.baseline + 1,
+            width: height + 1 + radicand.width,
+        };
+        match index {
+            Some(index) => {
+                // the last line of the index is just above the end of the radical, on its left
+                let index_height = index.lines.len();
+                let top = height.saturating_sub(index_height + radical.baseline);
+                let baseline = index_height + top + radical.baseline - height;
+                let bottom = (baseline + 1).saturating_sub(index_height + top);
+                let mut index = index.padded(top, bottom);
+                index.baseline = baseline;
+                Self::beside(vec![index, radical])
+            }
+            None => radical,
+        }
+    }
+    /// the cells of a matrix in rows of `columns` cells, with aligned columns, between brackets
+    fn matrix(cells: &[Self], columns: usize, color: Color) -> Self {
+        let widths: Vec<usize> = (0..columns)
+            .map(|column_n| {
+                cells
+                    .iter()
+                    .skip(column_n)
+                    .step_by(columns)
+                    .map(|cell| cell.width)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let mut lines = Vec::new();
+        for row in cells.chunks(columns) {
+            let mut pieces = Vec::new();
+            for (column_n, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                // numbers are usually aligned on the right
+                let separator = if column_n > 0 { "  " } else { "" };
+                let padding = " ".repeat(width - cell.width);
+                pieces.push(Self::text(format!("{}{}", separator, padding)));
+                pieces.push(cell.clone());
+            }
+            lines.extend(Self::beside(pieces).lines);
+        }
+        let matrix = Self {
+            baseline: (lines.len() - 1) / 2,
+            lines,
+            width: widths.iter().sum::<usize>() + 2 * (columns - 1),
+        };
+        matrix.delimited('[', ']', color)
+    }
+    /// the picture between delimiters as tall as it, e.g. `(` and `)`
+    fn delimited(self, left: char, right: char, color: Color) -> Self {
+        let height = self.lines.len();
+        let baseline = self.baseline;
+        Self::beside(vec![
+            delimiter(left, height, baseline, color),
+            self,
+            delimiter(right, height, baseline, color),
+        ])
+    }
+}
+
+/// the delimiter `c` drawn on `height` lines, or `None` if it can't be drawn on several lines
+fn delimiter_lines(c: char, height: usize) -> Option<Vec<&'static str>> {
+    let (top, middle, bottom, center) = match c {
+        '(' => ("\u{239b}", "\u{239c}", "\u{239d}", None),
+        ')' => ("\u{239e}", "\u{239f}", "\u{23a0}", None),
+        '[' => ("\u{23a1}", "\u{23a2}", "\u{23a3}", None),
+        ']' => ("\u{23a4}", "\u{23a5}", "\u{23a6}", None),
+        '{' => ("\u{23a7}", "\u{23aa}", "\u{23a9}", Some("\u{23a8}")),
+        '}' => ("\u{23ab}", "\u{23aa}", "\u{23ad}", Some("\u{23ac}")),
+        '|' => ("\u{2502}", "\u{2502}", "\u{2502}", None),
+        _ => return None,
+    };
+    Some(
+        (0..height)
+            .map(|line_n| match line_n {
+                0 => top,
+                n if n == height - 1 => bottom,
+                n if n == height / 2 => center.unwrap_or(middle),
+                _ => middle,
+            })
+            .collect(),
+    )
+}
+
+fn delimiter(c: char, height: usize, baseline: usize, color: Color) -> Picture {
+    let lines = match delimiter_lines(c, height) {
+        Some(lines) if height > 1 => lines
+            .into_iter()
+            .map(|line| line.color(color).to_string())
+            .collect(),
+        _ => {
+            let mut picture = Picture::text(c.to_string().color(color).to_string());
+            picture = picture.padded(baseline, height - baseline - 1);
+            return picture;
+        }
+    };
+    Picture {
+        lines,
+        baseline,
+        width: 1,
+    }
+}
+
+/// 2D form of `node`, see [`PoincareNode::pretty_print_2d`]
+pub(crate) fn picture(node: &PoincareNode, nesting_level: usize) -> Picture {
+    let color = PoincareNode::nesting_level_color(nesting_level);
+    let children: Vec<Picture> = node
+        .children
+        .iter()
+        .map(|child| picture(child, nesting_level + 1))
+        .collect();
+    if let Some(picture) = drawn_picture(node, &children, color) {
+        return picture;
+    }
+    if children.iter().all(|child| child.lines.len() == 1) {
+        return Picture::text(node.pretty_print(nesting_level, false).to_string());
+    }
+    // the short form, where the children are replaced by their pictures
+    let placeholders = (0..children.len())
+        .map(|child_n| format!("{}{}{}", PLACEHOLDER_START, child_n, PLACEHOLDER_END))
+        .collect();
+    let Some(text) = render::registry().render(node, placeholders) else {
+        return Picture::text(node.pretty_print(nesting_level, false).to_string());
+    };
+    let mut pieces = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut text_piece = String::new();
+    while let Some(c) = chars.next() {
+        if c != PLACEHOLDER_START {
+            text_piece.push(c);
+            continue;
+        }
+        let child_n: String = chars
+            .by_ref()
+            .take_while(|c| *c != PLACEHOLDER_END)
+            .collect();
+        let Some(child) = child_n.parse().ok().and_then(|n: usize| children.get(n)) else {
+            continue;
+        };
+        let mut child = child.clone();
+        // delimiters around a child on several lines are as tall as it
+        match (text_piece.chars().last(), chars.peek().copied()) {
+            (Some(left), Some(right)) if child.lines.len() > 1 && are_delimiters(left, right) => {
+                text_piece.pop();
+                chars.next();
+                child = child.delimited(left, right, color);
+            }
+            _ => {}
+        }
+        if !text_piece.is_empty() {
+            pieces.push(Picture::text(text_piece.color(color).to_string()));
+            text_piece.clear();
+        }
+        pieces.push(child);
+    }
+    if !text_piece.is_empty() {
+        pieces.push(Picture::text(text_piece.color(color).to_string()));
+    }
+    Picture::beside(pieces)
+}
+
+fn are_delimiters(left: char, right: char) -> bool {
+    matches!(
+        (left, right),
+        ('(', ')') | ('[', ']') | ('{', '}') | ('|', '|')
+    )
+}
+
+/// 2D form of the nodes which are drawn differently from their short form, e.g. fractions
+fn drawn_picture(node: &PoincareNode, children: &[Picture], color: Color) -> Option<Picture> {
+    Some(match (node.name.as_str(), children) {
+        ("Division" | "FractionLayout", [numerator, denominator]) => {
+            Picture::fraction(numerator.clone(), denominator.clone(), color)
+        }
+        ("Rational", []) => {
+            let Some(PoincareAttributes::Rational {
+                negative,
+                numerator,
+                denominator,
+            }) = &node.attributes
+            else {
+                return None;
+            };
+            if denominator == "1" {
+                // kept on one line, as in the short form
+                return None;
+            }
+            let text = |text: &str| Picture::text(text.color(color).to_string());
+            let fraction = Picture::fraction(text(numerator), text(denominator), color);
+            match negative.as_str() {
+                "0" => fraction,
+                "1" => Picture::beside(vec![text("-"), fraction]),
+                _ => Picture::beside(vec![text("sign?"), fraction]),
+            }
+        }
+        ("Power", [base, exponent]) => {
+            let base = if render::registry().precedence(&node.children[0]) <= Precedence::POWER {
+                base.clone().delimited('(', ')', color)
+            } else {
+                base.clone()
+            };
+            Picture::power(base, exponent.clone())
+        }
+        ("SquareRoot" | "NthRootLayout", [radicand]) => {
+            Picture::radical(radicand.clone(), None, color)
+        }
+        ("NthRoot" | "NthRootLayout", [radicand, index]) => {
+            Picture::radical(radicand.clone(), Some(index.clone()), color)
+        }
+        ("Matrix" | "MatrixLayout", _) => {
+            let (rows, columns) = node.matrix_dimensions()?;
+            // matrices with wrong dimensions are flagged in the short form
+            if rows == 0 || columns == 0 || rows * columns != children.len() {
+                return None;
+            }
+            Picture::matrix(children, columns, color)
+        }
+        ("VerticalOffsetLayout", [offset]) => {
+            // the base is the previous layout in the concatenation
+            match &node.attributes {
+                Some(PoincareAttributes::VerticalOffsetLayout { position })
+                    if position == "subscript" =>
+                {
+                    let mut lowered = offset.clone().padded(1, 0);
+                    lowered.baseline = 0;
+                    lowered
+                }
+                _ => {
+                    let height = offset.lines.len();
+                    let mut raised = offset.clone().padded(0, 1);
+                    raised.baseline = height;
+                    raised
+                }
+            }
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_log;
+    use crate::poincare::strip_colors;
+    use crate::reduction::ViewOptions;
+
+    /// the lines of the 2D form of the expression written in XML as `xml`, and its baseline
+    fn drawing(xml: &str) -> (Vec<String>, usize) {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            xml
+        );
+        let picture = parse_log(&log)
+            .unwrap()
+            .remove(0)
+            .before
+            .unwrap()
+            .pretty_print_2d(0);
+        let lines = picture
+            .lines
+            .iter()
+            .map(|line| strip_colors(line))
+            .collect();
+        (lines, picture.baseline)
+    }
+
+    fn node(name: &str, children: &[String]) -> String {
+        format!(r#"<{0} id="{0}">{1}</{0}>"#, name, children.concat())
+    }
+
+    fn symbol(name: &str) -> String {
+        format!(r#"<Symbol id="s" name="{}"></Symbol>"#, name)
+    }
+
+    fn integer(value: u32) -> String {
+        format!(r#"<Integer id="i" value="{}"></Integer>"#, value)
+    }
+
+    /// `x/2`
+    fn half_x() -> String {
+        node("Division", &[symbol("x"), integer(2)])
+    }
+
+    /// `lines` as owned strings, to be compared with a drawing
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn stacks_nested_fractions() {
+        let sum = node("Addition", &[integer(1), half_x()]);
+        let fraction = node(
+            "Division",
+            &[sum, node("Division", &[integer(3), symbol("y")])],
+        );
+        assert_eq!(
+            drawing(&fraction),
+            (
+                lines(&[
+                    "      x  ",
+                    " 1 + ─── ",
+                    "      2  ",
+                    "─────────",
+                    "    3    ",
+                    "   ───   ",
+                    "    y    ",
+                ]),
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn raises_the_exponent_of_a_fraction() {
+        assert_eq!(
+            drawing(&node("Power", &[half_x(), integer(2)])),
+            (lines(&["     2", "⎛ x ⎞ ", "⎜───⎟ ", "⎝ 2 ⎠ "]), 2)
+        );
+        assert_eq!(
+            drawing(&node("Power", &[symbol("e"), half_x()])),
+            (lines(&["  x ", " ───", "  2 ", "e   "]), 3)
+        );
+    }
+
+    #[test]
+    fn writes_the_index_of_a_radical_on_its_left() {
+        assert_eq!(
+            drawing(&node("NthRoot", &[symbol("x"), integer(3)])),
+            (lines(&["3  _", " ╲╱x"]), 1)
+        );
+        // the last line of the index is just above the end of the radical
+        assert_eq!(
+            drawing(&node("NthRoot", &[half_x(), integer(3)])),
+            (lines(&["     ___", "    ╱ x ", "3  ╱ ───", " ╲╱   2 "]), 2)
+        );
+        assert_eq!(
+            drawing(&node("NthRoot", &[symbol("x"), half_x()])),
+            (lines(&[" x    ", "───   ", " 2   _", "   ╲╱x"]), 3)
+        );
+    }
+
+    #[test]
+    fn aligns_the_columns_of_a_matrix() {
+        let matrix = format!(
+            r#"<Matrix id="m" rows="2" columns="2">{}{}{}{}</Matrix>"#,
+            integer(10),
+            symbol("x"),
+            half_x(),
+            integer(4)
+        );
+        assert_eq!(
+            drawing(&matrix),
+            (lines(&["⎡ 10  x⎤", "⎢ x    ⎥", "⎢───  4⎥", "⎣ 2    ⎦"]), 1)
+        );
+    }
+
+    #[test]
+    fn makes_delimiters_as_tall_as_their_content() {
+        assert_eq!(
+            drawing(&node("Cosine", &[half_x()])),
+            (lines(&["   ⎛ x ⎞", "cos⎜───⎟", "   ⎝ 2 ⎠"]), 1)
+        );
+        // a `Parenthesis` is written between braces in the short form
+        let sum = node("Addition", &[symbol("y"), half_x()]);
+        assert_eq!(
+            drawing(&node(
+                "Multiplication",
+                &[integer(2), node("Parenthesis", &[sum])]
+            )),
+            (
+                lines(&["    ⎧     x ⎫", "2 * ⎨y + ───⎬", "    ⎩     2 ⎭"]),
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_a_drawing_aligned_in_the_substeps() {
+        let sum = node("Addition", &[integer(1), half_x()]);
+        let log = format!(
+            r#"<Step name="simplify">
+  <State name="before">{0}</State>
+  <Step name="reduce">
+    <State name="before">{0}</State>
+    <State name="sign">{1}</State>
+    <State name="after">{1}</State>
+  </Step>
+  <State name="after">{1}</State>
+</Step>"#,
+            sum,
+            half_x()
+        );
+        let options = ViewOptions {
+            two_d: true,
+            ..ViewOptions::default()
+        };
+        let step = parse_log(&log).unwrap().remove(0);
+        assert_eq!(
+            strip_colors(&step.view(&options).to_string()),
+            [
+                "/> simplify ",
+                "|      x ",
+                "| 1 + ───",
+                "|      2 ",
+                "|    /> reduce ",
+                "|    |      x ",
+                "|    | 1 + ───",
+                "|    |      2 ",
+                "|    |         x ",
+                "|    |- sign: ───",
+                "|    |         2 ",
+                "|    |   x ",
+                "|    \\_ ───",
+                "|        2 ",
+                "|   x ",
+                "\\_ ───",
+                "    2 ",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -1,11 +1,18 @@
 use colored::*;
 
-use quick_xml::{
-    events::{BytesStart, Event},
-    reader::Reader,
-};
+use std::io::BufRead;
 
-use crate::{get_attribute_from_start, panic_event};
+use quick_xml::events::{BytesStart, Event};
+
+use crate::latex;
+use crate::picture::{self, Picture};
+use crate::reader::{get_attribute_from_start, EventReader};
+use crate::render;
+use crate::theme::theme;
+use crate::{Location, ParseError};
+
+/// Changes the output of a node, given the node and its nesting level
+pub type Decorator<'a> = dyn Fn(&PoincareNode, usize, ColoredString) -> ColoredString + 'a;
 
 #[derive(Debug, Clone)]
 pub struct PoincareNode {
@@ -13,53 +20,79 @@ pub struct PoincareNode {
     pub id: String,
     pub children: Vec<PoincareNode>,
     pub attributes: Option<PoincareAttributes>,
+    /// the log ends before the end of the node, so some of its children may be missing
+    pub incomplete: bool,
 }
 impl PoincareNode {
-    pub fn from_start(start: &BytesStart, pos: usize) -> Self {
-        Self {
-            name: String::from_utf8(start.name().as_ref().to_vec()).unwrap(),
-            id: get_attribute_from_start(start, b"id").unwrap_or_else(|| {
-                panic!(
-                    "no id for node {} at pos {}",
-                    String::from_utf8(start.name().as_ref().to_vec()).unwrap(),
-                    pos
-                )
-            }),
+    pub fn from_start(start: &BytesStart, pos: Location) -> Result<Self, ParseError> {
+        let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+        Ok(Self {
+            id: get_attribute_from_start(start, b"id")
+                .ok_or_else(|| ParseError::missing_attribute(pos, &name, "id"))?,
             attributes: PoincareAttributes::try_from_start(start),
             children: Vec::new(),
-        }
+            name,
+            incomplete: false,
+        })
     }
-    pub fn from_previous(reader: &mut Reader<&[u8]>) -> Self {
+    /// reads the start of a node, returning `None` if the log ends before it
+    pub fn from_previous<R: BufRead>(
+        reader: &mut EventReader<R>,
+    ) -> Result<Option<Self>, ParseError> {
         match reader.read_event() {
-            Ok(Event::Start(start)) => Self::from_start(&start, reader.buffer_position()),
-            other => panic_event(reader, other),
+            event if reader.is_truncation(&event) => Ok(None),
+            Err(e) => Err(ParseError::xml(reader.location(), e)),
+            Ok(Event::Start(start)) => Self::from_start(&start, reader.location()).map(Some),
+            Ok(ev) => Err(ParseError::unexpected(
+                reader.location(),
+                "start of a Poincare node",
+                &ev,
+            )),
         }
     }
-    pub fn build(&mut self, reader: &mut Reader<&[u8]>) {
+    /// reads the children of the node, returning `false` if the log ends before the node does
+    pub fn build<R: BufRead>(&mut self, reader: &mut EventReader<R>) -> Result<bool, ParseError> {
+        let complete = self
+            .build_children(reader)
+            .map_err(|e| e.within(format!("{}[id={}]", self.name, self.id)))?;
+        self.incomplete = !complete;
+        Ok(complete)
+    }
+    fn build_children<R: BufRead>(
+        &mut self,
+        reader: &mut EventReader<R>,
+    ) -> Result<bool, ParseError> {
         loop {
             match reader.read_event() {
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                Ok(Event::Eof) => break,
                 Ok(Event::Start(start)) => {
-                    let mut child = PoincareNode::from_start(&start, reader.buffer_position());
-                    child.build(reader);
+                    let mut child = PoincareNode::from_start(&start, reader.location())?;
+                    let complete = child.build(reader)?;
                     self.children.push(child);
-                }
-                Ok(Event::End(end)) => {
-                    if String::from_utf8(end.name().as_ref().to_vec()).unwrap() == self.name {
-                        break;
-                    } else {
-                        panic_event(
-                            reader,
-                            String::from_utf8(end.name().as_ref().to_vec()).unwrap(),
-                        );
+                    if !complete {
+                        return Ok(false);
                     }
                 }
-                Ok(ev) => panic_event(reader, ev),
+                Ok(Event::End(end)) if end.name().as_ref() == self.name.as_bytes() => {
+                    return Ok(true)
+                }
+                event if reader.is_truncation(&event) => return Ok(false),
+                Err(e) => return Err(ParseError::xml(reader.location(), e)),
+                Ok(ev) => {
+                    return Err(ParseError::unexpected(
+                        reader.location(),
+                        &format!("start of a Poincare node or end of `{}`", self.name),
+                        &ev,
+                    ))
+                }
             }
         }
     }
-    fn print_long_form(&self, nesting_level: usize, long_form_for_children: bool) -> ColoredString {
+    fn print_long_form(
+        &self,
+        nesting_level: usize,
+        long_form_for_children: bool,
+        decorate: &Decorator<'_>,
+    ) -> ColoredString {
         let mut output = String::new();
         let id_white_str = format!("({})", self.id).white();
         output.push_str(&format!("{}{}", self.name, id_white_str));
@@ -67,189 +100,222 @@ impl PoincareNode {
             let attributes_str = attributes.pretty_print().green();
             output.push_str(&format!(": {}", attributes_str));
         }
-        if self.children.len() > 0 {
+        if !self.children.is_empty() || self.incomplete {
             output.push_str(" { ");
             for child in &self.children {
                 output.push_str(&format!(
                     "{}, ",
-                    child.pretty_print(nesting_level + 1, long_form_for_children)
+                    child.decorated_print(nesting_level + 1, long_form_for_children, decorate)
                 ));
             }
-            output.push_str("}");
+            if self.incomplete {
+                output.push_str("\u{2026}, ");
+            }
+            output.push('}');
         }
         output.color(Self::nesting_level_color(nesting_level))
     }
     pub fn pretty_print(&self, nesting_level: usize, long_form: bool) -> ColoredString {
+        self.decorated_print(nesting_level, long_form, &|_, _, output| output)
+    }
+    /// like [`PoincareNode::pretty_print`], but the output of each node goes through `decorate`,
+    /// e.g. to highlight some nodes
+    pub fn decorated_print(
+        &self,
+        nesting_level: usize,
+        long_form: bool,
+        decorate: &Decorator<'_>,
+    ) -> ColoredString {
+        let output = self.undecorated_print(nesting_level, long_form, decorate);
+        decorate(self, nesting_level, output)
+    }
+    /// LaTeX form of the expression, to be used in math mode, e.g. `\frac{1}{2}` for a `Division`
+    pub fn latex(&self) -> String {
+        latex::latex(self)
+    }
+    /// the expression drawn on several lines, with fractions, exponents and radicals in 2D
+    pub fn pretty_print_2d(&self, nesting_level: usize) -> Picture {
+        picture::picture(self, nesting_level)
+    }
+    fn undecorated_print(
+        &self,
+        nesting_level: usize,
+        long_form: bool,
+        decorate: &Decorator<'_>,
+    ) -> ColoredString {
         if long_form {
-            return self.print_long_form(nesting_level, long_form);
+            return self.print_long_form(nesting_level, long_form, decorate);
         }
-        let mut output = String::new();
-        let mut children_output: Vec<ColoredString> = Vec::new();
-        if self.children.len() > 0 {
-            for child in &self.children {
-                children_output.push(child.pretty_print(nesting_level + 1, long_form));
-            }
+        let registry = render::registry();
+        if registry.get(&self.name).is_none() {
+            // default to full log when nothing else is available
+            return self.print_long_form(nesting_level, false, decorate);
         }
-        'types: {
-            // TODO: abstract this
-            match self.name.as_str() {
-                // only display attributes for these nodes
-                "Symbol" | "SymbolAbstract" | "Sequence" | "Function" | "Constant"
-                | "BasedInteger" | "Decimal" | "Float" | "Integer" | "Rational" => {
-                    if let Some(attr) = &self.attributes {
-                        output.push_str(&attr.pretty_print());
-                        break 'types;
-                    }
-                }
-                _ => (),
-            }
-            // TODO: abstract this
-            let nary_operation: Option<&str> = match self.name.as_str() {
-                "Addition" => Some("+"),
-                "Subtraction" => Some("-"),
-                "Multiplication" => Some("*"),
-                "Division" => Some("/"),
-                "Power" => Some("^"),
-                _ => None,
-            };
-            if let Some(op) = nary_operation {
-                for (child_n, child_str) in children_output.iter().enumerate() {
-                    // only add parentheses when the child has more than one children
-                    let child_str = if child_n == children_output.len() - 1 {
-                        if self.children[child_n].children.len() > 1 {
-                            format!("({})", &child_str)
-                        } else {
-                            format!("{}", &child_str)
-                        }
-                    } else {
-                        if self.children[child_n].children.len() > 1 {
-                            format!("({}) {} ", child_str, op)
-                        } else {
-                            format!("{} {} ", child_str, op)
-                        }
-                    };
-                    output.push_str(&child_str);
-                }
-                break 'types;
-            }
-            let prefixed_function: Option<&str> = match self.name.as_str() {
-                // TODO: complete the list
-                "AbsoluteValue" => Some("abs"),
-                "ArcCosine" => Some("acos"),
-                "ArcSine" => Some("asin"),
-                "ArcTangent" => Some("atan"),
-                "BinomCDF" => Some("bCDF"),
-                "BinomPDF" => Some("bPDF"),
-                "Ceiling" => Some("ceil"),
-                "Conjugate" => Some("conj"),
-                "Cosine" => Some("cos"),
-                "Derivative" => Some("der"),
-                "Floor" => Some("floor"),
-                "FracPart" => Some("frac"),
-                "GreatCommonDivisor" => Some("gcd"),
-                "HyperbolicArcCosine" => Some("hacos"),
-                "HyperbolicArcSine" => Some("hasin"),
-                "HyperbolicArcTangent" => Some("hatan"),
-                "HyperbolicCosine" => Some("hcos"),
-                "HyperbolicSine" => Some("hsin"),
-                "HyperbolicTangent" => Some("htan"),
-                "ImaginaryPart" => Some("imag"),
-                "LeastCommonMultiple" => Some("lcm"),
-                "Integral" => Some("int"),
-                "Logarithm" => Some("log"),
-                "Opposite" => Some("-"),
-                "Randint" => Some("randint"),
-                "Random" => Some("rand"),
-                "RealPart" => Some("real"),
-                "Round" => Some("round"),
-                "SignFunction" => Some("sign"),
-                "Sine" => Some("sin"),
-                "Tangent" => Some("tan"),
-                "SquareRoot" => Some("sqrt"),
-                "NaperianLogarithm" => Some("ln"),
-                _ => None,
-            };
-            if let Some(f) = prefixed_function {
-                for (child_n, child_str) in children_output.iter().enumerate() {
-                    if child_n == 0 {
-                        output.push_str(&format!("{}({}", f, child_str));
-                    } else {
-                        output.push_str(&format!(", {}", child_str));
-                    }
-                    if child_n == children_output.len() - 1 {
-                        output.push_str(")");
-                    }
-                }
-                break 'types;
-            }
-            let plain_name: Option<&str> = match self.name.as_str() {
-                "Undefined" => Some("undef"),
-                _ => None,
-            };
-            if let Some(name) = plain_name {
-                output.push_str(name);
-                break 'types;
-            }
-
-            // formats specific to one node
-            match self.name.as_str() {
-                "Parenthesis" => {
-                    if self.children.len() != 1 {
-                        panic!("ParenthesisNode can only have exactly one child");
-                    }
-                    // display {} for ParenthesisNode to differentiate it from other parentheses
-                    output.push_str(&format!("{{{}}}", children_output.first().unwrap()));
-                }
-                // default to full log when nothing else is available
-                _ => output.push_str(&self.print_long_form(nesting_level, false)),
+        let children_output: Vec<String> = self
+            .children
+            .iter()
+            .map(|child| {
+                child
+                    .decorated_print(nesting_level + 1, long_form, decorate)
+                    .to_string()
+            })
+            .collect();
+        match registry.render(self, children_output) {
+            Some(output) => output.color(Self::nesting_level_color(nesting_level)),
+            None => self.print_long_form(nesting_level, false, decorate),
+        }
+    }
+    /// number of rows and columns of a `Matrix`, if it has valid dimensions
+    pub fn matrix_dimensions(&self) -> Option<(usize, usize)> {
+        match &self.attributes {
+            Some(PoincareAttributes::Matrix { rows, columns }) => {
+                Some((rows.parse().ok()?, columns.parse().ok()?))
             }
+            _ => None,
         }
-        output.color(Self::nesting_level_color(nesting_level))
     }
-    fn nesting_level_color(level: usize) -> String {
-        let level = level % 3;
-        String::from(match level {
-            0 => "yellow",
-            1 => "magenta",
-            2 => "blue",
-            _ => unreachable!(),
-        })
+    /// the node of the expression whose id is `id`
+    pub fn find(&self, id: &str) -> Option<&PoincareNode> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+    /// the matrices of the expression which aren't inside another matrix
+    pub fn outermost_matrices(&self) -> Vec<&PoincareNode> {
+        if self.name == "Matrix" || self.name == "MatrixLayout" {
+            return vec![self];
+        }
+        self.children
+            .iter()
+            .flat_map(|child| child.outermost_matrices())
+            .collect()
+    }
+    /// lines of a multi-line rendering of a `Matrix`, with aligned columns, e.g.
+    /// ```text
+    /// ⎡1  x + 1⎤
+    /// ⎣3      4⎦
+    /// ```
+    ///
+    /// Returns `None` if the node isn't a matrix whose number of children matches its dimensions.
+    pub fn matrix_box(&self, nesting_level: usize, long_form: bool) -> Option<Vec<String>> {
+        let (rows, columns) = self.matrix_dimensions()?;
+        if rows == 0 || columns == 0 || rows * columns != self.children.len() {
+            return None;
+        }
+        let cells: Vec<String> = self
+            .children
+            .iter()
+            .map(|child| child.pretty_print(nesting_level + 1, long_form).to_string())
+            .collect();
+        let widths: Vec<usize> = (0..columns)
+            .map(|column_n| {
+                cells
+                    .iter()
+                    .skip(column_n)
+                    .step_by(columns)
+                    .map(|cell| visible_width(cell))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let color = Self::nesting_level_color(nesting_level);
+        let lines = cells
+            .chunks(columns)
+            .enumerate()
+            .map(|(row_n, row)| {
+                let (left, right) = match row_n {
+                    _ if rows == 1 => ("[", "]"),
+                    0 => ("\u{23a1}", "\u{23a4}"),
+                    n if n == rows - 1 => ("\u{23a3}", "\u{23a6}"),
+                    _ => ("\u{23a2}", "\u{23a5}"),
+                };
+                let row_cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| {
+                        // numbers are usually aligned on the right
+                        let padding = " ".repeat(width - visible_width(cell));
+                        format!("{}{}", padding, cell)
+                    })
+                    .collect();
+                format!(
+                    "{}{}{}",
+                    left.color(color),
+                    row_cells.join("  "),
+                    right.color(color)
+                )
+            })
+            .collect();
+        Some(lines)
+    }
+    /// compares the nodes and their children recursively, according to `mode`
+    pub fn equals(&self, other: &Self, mode: CompareMode) -> bool {
+        let same_identity = self.id == other.id;
+        let same_value = self.name == other.name && self.attributes == other.attributes;
+        let same_node = match mode {
+            CompareMode::Identity => same_identity,
+            CompareMode::Structural => same_value,
+            CompareMode::Full => same_identity && same_value,
+        };
+        // a child may have been added or removed
+        same_node
+            && self.children.len() == other.children.len()
+            && self
+                .children
+                .iter()
+                .zip(&other.children)
+                .all(|(lhs_child, rhs_child)| lhs_child.equals(rhs_child, mode))
+    }
+    pub(crate) fn nesting_level_color(level: usize) -> Color {
+        theme().nesting_color(level)
     }
 }
 impl PartialEq for PoincareNode {
     fn eq(&self, other: &Self) -> bool {
-        let lhs: u32 = self
-            .id
-            .parse()
-            .expect("a node id should be a positive number");
-        let rhs: u32 = other
-            .id
-            .parse()
-            .expect("a node id should be a positive number");
-        if lhs != rhs {
-            return false;
-        }
-        // comparing children rely on the iterators yielding items in a well-defined order
-        let lhs_children = self.children.iter();
-        let rhs_children = other.children.iter();
-        for (lhs_child, rhs_child) in lhs_children.zip(rhs_children) {
-            if lhs_child != rhs_child {
-                return false;
-            }
-        }
-        true
+        self.equals(other, CompareMode::Full)
     }
 }
 
-#[derive(Debug, Clone)]
+/// What must be the same for two nodes to be considered equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompareMode {
+    /// the ids of the nodes and of their children, which tells whether the nodes are the same
+    /// objects in Poincare, even if their values changed in place
+    Identity,
+    /// the names, attributes and children of the nodes, whatever their ids, which tells whether
+    /// the nodes are displayed the same
+    Structural,
+    /// both the identity and the structure
+    #[default]
+    Full,
+}
+impl CompareMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "identity" => Self::Identity,
+            "structural" => Self::Structural,
+            "full" => Self::Full,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PoincareAttributes {
     BasedInteger {
         base: String,
         integer: String,
     },
+    Boolean {
+        value: String,
+    },
     CodePointLayout {
         code_point: String,
     },
+    Comparison {
+        operator: String,
+    },
     Decimal {
         negative: String,
         mantissa: String,
@@ -280,6 +346,9 @@ pub enum PoincareAttributes {
         prefix: String,
         root_symbol: String,
     },
+    VerticalOffsetLayout {
+        position: String,
+    },
 }
 impl PoincareAttributes {
     pub fn try_from_start(start: &BytesStart) -> Option<Self> {
@@ -289,9 +358,15 @@ impl PoincareAttributes {
                 base: get_attribute_from_start(start, b"base")?,
                 integer: get_attribute_from_start(start, b"integer")?,
             }),
+            b"Boolean" => Some(Self::Boolean {
+                value: get_attribute_from_start(start, b"value")?,
+            }),
             b"CodePointLayout" => Some(Self::CodePointLayout {
                 code_point: get_attribute_from_start(start, b"CodePoint")?,
             }),
+            b"Comparison" => Some(Self::Comparison {
+                operator: get_attribute_from_start(start, b"operator")?,
+            }),
             b"Decimal" => Some(Self::Decimal {
                 negative: get_attribute_from_start(start, b"negative")?,
                 mantissa: get_attribute_from_start(start, b"mantissa")?,
@@ -306,7 +381,7 @@ impl PoincareAttributes {
             b"Integer" => Some(Self::Integer {
                 value: get_attribute_from_start(start, b"value")?,
             }),
-            b"Matrix" => Some(Self::Matrix {
+            b"Matrix" | b"MatrixLayout" => Some(Self::Matrix {
                 rows: get_attribute_from_start(start, b"rows")?,
                 columns: get_attribute_from_start(start, b"columns")?,
             }),
@@ -326,13 +401,62 @@ impl PoincareAttributes {
                 prefix: get_attribute_from_start(start, b"prefix")?,
                 root_symbol: get_attribute_from_start(start, b"rootSymbol")?,
             }),
+            b"VerticalOffsetLayout" => Some(Self::VerticalOffsetLayout {
+                position: get_attribute_from_start(start, b"position")?,
+            }),
             _ => None,
         }
     }
+    /// the attributes, as named in the log, with their values, in the order of the log
+    pub fn pairs(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            Self::BasedInteger { base, integer } => vec![("base", base), ("integer", integer)],
+            Self::Boolean { value } => vec![("value", value)],
+            Self::CodePointLayout { code_point } => vec![("CodePoint", code_point)],
+            Self::Comparison { operator } => vec![("operator", operator)],
+            Self::Decimal {
+                negative,
+                mantissa,
+                exponent,
+            } => vec![
+                ("negative", negative),
+                ("mantissa", mantissa),
+                ("exponent", exponent),
+            ],
+            Self::Float { value } => vec![("value", value)],
+            Self::Infinity { negative } => vec![("negative", negative)],
+            Self::Integer { value } => vec![("value", value)],
+            Self::Matrix { rows, columns } => vec![("rows", rows), ("columns", columns)],
+            Self::Rational {
+                negative,
+                numerator,
+                denominator,
+            } => vec![
+                ("negative", negative),
+                ("numerator", numerator),
+                ("denominator", denominator),
+            ],
+            Self::SymbolAbstract { name } => vec![("name", name)],
+            Self::Unit {
+                prefix,
+                root_symbol,
+            } => vec![("prefix", prefix), ("rootSymbol", root_symbol)],
+            Self::VerticalOffsetLayout { position } => vec![("position", position)],
+        }
+    }
+    /// value of the attribute called `name` in the log, e.g. `denominator` for a `Rational`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs()
+            .into_iter()
+            .find(|(attribute, _)| *attribute == name)
+            .map(|(_, value)| value)
+    }
     pub fn pretty_print(&self) -> String {
         match self {
             Self::BasedInteger { base, integer } => format!("{}__{}", integer, base),
-            Self::CodePointLayout { code_point } => format!("{}", code_point),
+            Self::Boolean { value } => value.to_string(),
+            Self::CodePointLayout { code_point } => code_point.to_string(),
+            Self::Comparison { operator } => operator.to_string(),
             Self::Decimal {
                 negative,
                 mantissa,
@@ -349,7 +473,7 @@ impl PoincareAttributes {
                 mantissa,
                 exponent
             ),
-            Self::Float { value } => format!("{}", value),
+            Self::Float { value } => value.to_string(),
             Self::Infinity { negative } => format!(
                 "{}inf",
                 if negative == "0" {
@@ -360,7 +484,7 @@ impl PoincareAttributes {
                     "sign?"
                 }
             ),
-            Self::Integer { value } => format!("{}", value),
+            Self::Integer { value } => value.to_string(),
             Self::Matrix { rows, columns } => format!("rows: {}, columns: {}", rows, columns),
             Self::Rational {
                 negative,
@@ -378,11 +502,122 @@ impl PoincareAttributes {
                 numerator,
                 denominator
             ),
-            Self::SymbolAbstract { name } => format!("{}", name),
+            Self::SymbolAbstract { name } => name.to_string(),
             Self::Unit {
                 prefix,
                 root_symbol,
             } => format!("{}{}", prefix, root_symbol),
+            Self::VerticalOffsetLayout { position } => position.to_string(),
+        }
+    }
+}
+
+/// `text` without the escape sequences of the colors
+pub(crate) fn strip_colors(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // an escape sequence ends with a letter, e.g. `\x1b[1;33m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// number of characters displayed by `text`, without the escape sequences of the colors
+pub(crate) fn visible_width(text: &str) -> usize {
+    strip_colors(text).chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    fn node(xml: &str) -> PoincareNode {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            xml
+        );
+        parse_log(&log).unwrap().remove(0).before.unwrap()
+    }
+
+    fn half(id: &str, numerator: &str) -> String {
+        format!(
+            r#"<Rational id="{}" negative="0" numerator="{}" denominator="2"></Rational>"#,
+            id, numerator
+        )
+    }
+
+    /// `x + <rational>`, where the ids of the addition and `x` are given by `ids`
+    fn sum(ids: (&str, &str), rational: &str) -> PoincareNode {
+        node(&format!(
+            r#"<Addition id="{}"><Symbol id="{}" name="x"></Symbol>{}</Addition>"#,
+            ids.0, ids.1, rational
+        ))
+    }
+
+    #[test]
+    fn compares_the_same_expression() {
+        let expression = sum(("1", "2"), &half("3", "1"));
+        for mode in [
+            CompareMode::Identity,
+            CompareMode::Structural,
+            CompareMode::Full,
+        ] {
+            assert!(expression.equals(&expression.clone(), mode));
         }
     }
+
+    #[test]
+    fn compares_a_value_changed_in_place() {
+        let before = sum(("1", "2"), &half("3", "1"));
+        let after = sum(("1", "2"), &half("3", "3"));
+        assert!(before.equals(&after, CompareMode::Identity));
+        assert!(!before.equals(&after, CompareMode::Structural));
+        assert!(!before.equals(&after, CompareMode::Full));
+    }
+
+    #[test]
+    fn compares_a_copy() {
+        let before = sum(("1", "2"), &half("3", "1"));
+        let after = sum(("4", "5"), &half("6", "1"));
+        assert!(!before.equals(&after, CompareMode::Identity));
+        assert!(before.equals(&after, CompareMode::Structural));
+        assert!(!before.equals(&after, CompareMode::Full));
+    }
+
+    #[test]
+    fn compares_the_children() {
+        let before = sum(("1", "2"), &half("3", "1"));
+        let after = node(r#"<Addition id="1"><Symbol id="2" name="x"></Symbol></Addition>"#);
+        for mode in [
+            CompareMode::Identity,
+            CompareMode::Structural,
+            CompareMode::Full,
+        ] {
+            assert!(!before.equals(&after, mode));
+        }
+    }
+
+    #[test]
+    fn reads_the_compare_modes() {
+        assert_eq!(
+            CompareMode::from_name("identity"),
+            Some(CompareMode::Identity)
+        );
+        assert_eq!(
+            CompareMode::from_name("structural"),
+            Some(CompareMode::Structural)
+        );
+        assert_eq!(CompareMode::from_name("full"), Some(CompareMode::Full));
+        assert_eq!(CompareMode::from_name("exact"), None);
+    }
 }
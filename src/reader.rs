@@ -0,0 +1,362 @@
+use std::io::{self, BufRead, Read};
+use std::thread;
+use std::time::Duration;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    reader::Reader,
+};
+
+use crate::error::{Location, ParseError};
+use crate::reduction::StepNode;
+
+/// Reads the XML events of a log from any buffered source, keeping track of the current location.
+pub struct EventReader<R: BufRead> {
+    reader: Reader<LineCounter<R>>,
+    buf: Vec<u8>,
+}
+impl<R: BufRead> EventReader<R> {
+    pub fn new(source: R) -> Self {
+        let mut reader = Reader::from_reader(LineCounter::new(source));
+        reader.trim_text(true);
+        // end names are checked when building the nodes, which keeps the reader able to skip
+        // a broken step
+        reader.check_end_names(false);
+        Self {
+            reader,
+            buf: Vec::new(),
+        }
+    }
+    /// reads the next event, which is owned so that the nodes can be built while holding it
+    pub(crate) fn read_event(&mut self) -> Result<Event<'static>, quick_xml::Error> {
+        self.buf.clear();
+        let event = self.reader.read_event_into(&mut self.buf)?.into_owned();
+        // an element starting at the end of the log can't be complete, and its start tag may even
+        // have been cut
+        if let Event::Start(_) = event {
+            if self.at_end() {
+                return Err(quick_xml::Error::UnexpectedEof(String::from("Element")));
+            }
+        }
+        Ok(event)
+    }
+    fn at_end(&mut self) -> bool {
+        match self.reader.get_mut().fill_buf() {
+            Ok(rest) => rest.is_empty(),
+            Err(_) => true,
+        }
+    }
+    /// location just after the last event read
+    pub fn location(&self) -> Location {
+        self.reader.get_ref().location()
+    }
+    /// true if the log ends with `event`, possibly in the middle of an element
+    ///
+    /// This must be checked after the expected end events, as an end tag cut by the end of the
+    /// log is still read as an end event.
+    pub(crate) fn is_truncation(&mut self, event: &Result<Event, quick_xml::Error>) -> bool {
+        match event {
+            Ok(Event::Eof) | Err(quick_xml::Error::UnexpectedEof(_)) => true,
+            Ok(Event::End(_)) => self.at_end(),
+            _ => false,
+        }
+    }
+}
+
+/// Counts the lines of the bytes consumed from a buffered source.
+struct LineCounter<R: BufRead> {
+    inner: R,
+    offset: usize,
+    line: usize,
+    line_start: usize,
+}
+impl<R: BufRead> LineCounter<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+    fn location(&self) -> Location {
+        Location {
+            offset: self.offset,
+            line: self.line,
+            column: self.offset - self.line_start + 1,
+        }
+    }
+}
+impl<R: BufRead> Read for LineCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let amt = available.len().min(buf.len());
+        buf[..amt].copy_from_slice(&available[..amt]);
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+impl<R: BufRead> BufRead for LineCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        // the bytes being consumed are still in the buffer of the source
+        if let Ok(available) = self.inner.fill_buf() {
+            for (n, byte) in available[..amt].iter().enumerate() {
+                if *byte == b'\n' {
+                    self.line += 1;
+                    self.line_start = self.offset + n + 1;
+                }
+            }
+        }
+        self.offset += amt;
+        self.inner.consume(amt);
+    }
+}
+
+/// Reads a log which is still being written, like `tail -f`: instead of reaching the end of the
+/// log, reading waits for more data to be appended.
+pub struct Follow<R: Read> {
+    inner: R,
+    poll_interval: Duration,
+}
+impl<R: Read> Follow<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            poll_interval: Duration::from_millis(200),
+        }
+    }
+    /// time to wait before checking again for new data
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+}
+impl<R: Read> Read for Follow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let amt = self.inner.read(buf)?;
+            if amt > 0 || buf.is_empty() {
+                return Ok(amt);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+/// Iterator over the top-level steps of a log, yielding each step as soon as it has been read.
+///
+/// By default the iteration stops at the first error. In recovery mode broken top-level steps
+/// are skipped instead, and the errors are kept in [`StepReader::skipped`].
+pub struct StepReader<R: BufRead> {
+    reader: EventReader<R>,
+    recover: bool,
+    skipped: Vec<ParseError>,
+    finished: bool,
+}
+impl<R: BufRead> StepReader<R> {
+    pub fn new(source: R) -> Self {
+        Self {
+            reader: EventReader::new(source),
+            recover: false,
+            skipped: Vec::new(),
+            finished: false,
+        }
+    }
+    /// skip the top-level steps which can't be parsed instead of stopping
+    pub fn recover(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+    /// errors of the steps skipped in recovery mode
+    pub fn skipped(&self) -> &[ParseError] {
+        &self.skipped
+    }
+    fn read_step(&mut self) -> Option<Result<StepNode, ParseError>> {
+        let reader = &mut self.reader;
+        match reader.read_event() {
+            Ok(Event::Start(start)) if start.name().as_ref() == b"Step" => {
+                let step = StepNode::from_start(&start, reader.location())
+                    .and_then(|mut step| step.build(reader).map(|_| step));
+                Some(step)
+            }
+            event if reader.is_truncation(&event) => None,
+            Err(e) => Some(Err(ParseError::xml(reader.location(), e))),
+            Ok(ev) => Some(Err(ParseError::unexpected(
+                reader.location(),
+                "start of `Step`",
+                &ev,
+            ))),
+        }
+    }
+    /// reads events until the elements left open by `error` are closed
+    fn skip_broken_step(&mut self, error: &ParseError) {
+        let mut depth = error.depth();
+        while depth > 0 {
+            let offset = self.reader.location().offset;
+            match self.reader.read_event() {
+                Ok(Event::Start(_)) => depth += 1,
+                Ok(Event::End(_)) => depth -= 1,
+                Ok(Event::Eof) => break,
+                Ok(_) => (),
+                // stop if the reader can't go past the broken part of the log
+                Err(_) if self.reader.location().offset == offset => break,
+                Err(_) => (),
+            }
+        }
+    }
+}
+impl<R: BufRead> Iterator for StepReader<R> {
+    type Item = Result<StepNode, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.read_step() {
+                None => self.finished = true,
+                Some(Ok(step)) => return Some(Ok(step)),
+                Some(Err(error)) => {
+                    // an error at the same place as the previous one means the reader is stuck
+                    let stuck = self.skipped.last().map(|e| e.location) == Some(error.location);
+                    if !self.recover || stuck {
+                        self.finished = true;
+                        return Some(Err(error));
+                    }
+                    self.skip_broken_step(&error);
+                    self.skipped.push(error);
+                }
+            }
+        }
+        None
+    }
+}
+
+pub(crate) fn get_attribute_from_start(start: &BytesStart, attr_name: &[u8]) -> Option<String> {
+    let mut value: Option<String> = None;
+    // malformed attributes are ignored
+    for attr in start.attributes().flatten() {
+        if attr.key.as_ref() == attr_name {
+            value = Some(match attr.unescape_value() {
+                Ok(unescaped) => unescaped.into_owned(),
+                Err(_) => String::from_utf8_lossy(&attr.value).into_owned(),
+            });
+            break;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorKind;
+    use crate::parse_log;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    const LOG: &str = r#"<Step name="first">
+  <State name="before"><Symbol id="1" name="x"></Symbol></State>
+  <State name="after"><Symbol id="1" name="x"></Symbol></State>
+</Step>
+<Step name="broken">
+  <State name="before"><Addition id="2"><Symbol name="y"></Symbol></Addition></State>
+</Step>
+<Step name="last">
+  <State name="before"><Symbol id="4" name="z"></Symbol></State>
+</Step>
+"#;
+
+    #[test]
+    fn parses_the_steps() {
+        let steps = parse_log(&LOG[..LOG.find("<Step name=\"broken\"").unwrap()]).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].name, "first");
+        assert_eq!(steps[0].before.as_ref().unwrap().id, "1");
+        assert!(!steps[0].incomplete);
+    }
+
+    #[test]
+    fn reports_where_the_log_is_broken() {
+        let error = parse_log(LOG).unwrap_err();
+        assert_eq!(error.location.line, 6);
+        assert_eq!(
+            error.path,
+            ["Step[name=broken]", "State[name=before]", "Addition[id=2]"]
+        );
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MissingAttribute { .. }
+        ));
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let results: Vec<_> = StepReader::new(LOG.as_bytes()).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn skips_the_broken_steps_when_recovering() {
+        let mut reader = StepReader::new(LOG.as_bytes()).recover(true);
+        let names: Vec<String> = reader.by_ref().map(|step| step.unwrap().name).collect();
+        assert_eq!(names, ["first", "last"]);
+        assert_eq!(reader.skipped().len(), 1);
+        assert_eq!(reader.skipped()[0].location.line, 6);
+    }
+
+    #[test]
+    fn reports_unexpected_elements() {
+        let error = parse_log("<Step name=\"step\">\n  <Foo></Foo>\n</Step>\n").unwrap_err();
+        assert_eq!(error.location.line, 2);
+        assert_eq!(error.path, ["Step[name=step]"]);
+        assert!(matches!(error.kind, ParseErrorKind::UnexpectedEvent { .. }));
+    }
+
+    /// A log being written, where each chunk is only appended once everything before it has
+    /// been read, and a read found nothing new
+    struct GrowingLog {
+        written: VecDeque<u8>,
+        chunks: Rc<RefCell<VecDeque<&'static str>>>,
+    }
+    impl Read for GrowingLog {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.written.is_empty() {
+                if let Some(chunk) = self.chunks.borrow_mut().pop_front() {
+                    self.written.extend(chunk.bytes());
+                }
+                return Ok(0);
+            }
+            self.written.read(buf)
+        }
+    }
+
+    #[test]
+    fn waits_for_the_steps_appended_to_the_log() {
+        let first = LOG.find("<Step name=\"broken\"").unwrap();
+        let last = LOG.find("<Step name=\"last\"").unwrap();
+        // the last step is cut while it's being written
+        let cut = last + LOG[last..].find("<Symbol").unwrap();
+        let chunks = Rc::new(RefCell::new(VecDeque::from([
+            &LOG[..first],
+            &LOG[last..cut],
+            &LOG[cut..],
+        ])));
+        let log = GrowingLog {
+            written: VecDeque::new(),
+            chunks: chunks.clone(),
+        };
+        let follow = Follow::new(log).poll_interval(Duration::from_millis(1));
+        let mut reader = StepReader::new(io::BufReader::new(follow));
+        assert_eq!(reader.next().unwrap().unwrap().name, "first");
+        // the step is yielded without waiting for the rest of the log
+        assert_eq!(chunks.borrow().len(), 2);
+        let step = reader.next().unwrap().unwrap();
+        assert_eq!(step.name, "last");
+        assert!(!step.incomplete);
+        assert!(chunks.borrow().is_empty());
+    }
+}
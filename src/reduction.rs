@@ -1,15 +1,20 @@
 use colored::*;
-use std::fmt::{self, Debug, Display, Write};
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Write};
+use std::io::BufRead;
+use std::mem;
 
-use quick_xml::{
-    events::{BytesStart, Event},
-    reader::Reader,
-};
+use quick_xml::events::{BytesStart, Event};
 
 use indenter::indented;
 
-use crate::poincare::PoincareNode;
-use crate::{get_attribute_from_start, panic_event};
+use crate::diff::TreeDiff;
+use crate::filter::NamePattern;
+use crate::picture::Picture;
+use crate::poincare::{visible_width, CompareMode, PoincareNode};
+use crate::reader::{get_attribute_from_start, EventReader};
+use crate::theme::theme;
+use crate::{Location, ParseError};
 
 #[derive(Debug, Clone)]
 pub struct StepNode {
@@ -17,94 +22,270 @@ pub struct StepNode {
     pub after: Option<PoincareNode>,
     pub parts: Vec<StepPart>,
     pub name: String,
+    /// the log ends before the end of the step, e.g. because the simulator crashed
+    pub incomplete: bool,
 }
 impl StepNode {
-    pub fn from_start(start: &BytesStart) -> Self {
-        Self {
+    pub fn from_start(start: &BytesStart, pos: Location) -> Result<Self, ParseError> {
+        Ok(Self {
             before: None,
             after: None,
             parts: Vec::new(),
-            name: get_attribute_from_start(start, b"name").expect("A step must have a name"),
-        }
+            name: get_attribute_from_start(start, b"name")
+                .ok_or_else(|| ParseError::missing_attribute(pos, "Step", "name"))?,
+            incomplete: false,
+        })
+    }
+    /// reads the content of the step, as far as possible if the log is truncated
+    pub fn build<R: BufRead>(&mut self, reader: &mut EventReader<R>) -> Result<(), ParseError> {
+        self.build_parts(reader)
+            .map_err(|e| e.within(format!("Step[name={}]", self.name)))
     }
-    pub fn build(&mut self, reader: &mut Reader<&[u8]>) {
+    fn build_parts<R: BufRead>(&mut self, reader: &mut EventReader<R>) -> Result<(), ParseError> {
+        const EXPECTED: &str = "start of `Step` or `State`, or end of `Step`";
         loop {
             match reader.read_event() {
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                Ok(Event::Eof) => break,
                 Ok(Event::Start(start)) => match start.name().as_ref() {
                     b"Step" => {
-                        let mut substep = StepNode::from_start(&start);
-                        substep.build(reader);
+                        let mut substep = StepNode::from_start(&start, reader.location())?;
+                        substep.build(reader)?;
+                        let complete = !substep.incomplete;
                         self.parts.push(StepPart::Substep(substep));
+                        if !complete {
+                            self.incomplete = true;
+                            break;
+                        }
                     }
                     b"State" => {
-                        let mut poincare_node = PoincareNode::from_previous(reader);
-                        poincare_node.build(reader);
                         let state_name = get_attribute_from_start(&start, b"name");
-                        match state_name {
-                            Some(name) if name == "before" => self.before = Some(poincare_node),
-                            Some(name) if name == "after" => self.after = Some(poincare_node),
-                            name => self.parts.push(StepPart::State(name, poincare_node)),
+                        let (poincare_node, complete) = Self::build_state(reader).map_err(|e| {
+                            e.within(match &state_name {
+                                Some(name) => format!("State[name={}]", name),
+                                None => String::from("State"),
+                            })
+                        })?;
+                        if let Some(poincare_node) = poincare_node {
+                            match state_name {
+                                Some(name) if name == "before" => self.before = Some(poincare_node),
+                                Some(name) if name == "after" => self.after = Some(poincare_node),
+                                name => self.parts.push(StepPart::State(name, poincare_node)),
+                            }
                         }
-                        match reader.read_event() {
-                            Ok(Event::End(end)) => match end.name().as_ref() {
-                                b"State" => (),
-                                string => panic_event(
-                                    reader,
-                                    String::from_utf8(string.to_vec()).unwrap(),
-                                ),
-                            },
-                            other => panic_event(reader, other),
+                        if !complete {
+                            self.incomplete = true;
+                            break;
                         }
                     }
-                    start => panic_event(reader, String::from_utf8(start.to_vec()).unwrap()),
-                },
-                Ok(Event::End(end)) => match end.name().as_ref() {
-                    b"Step" => break,
-                    string => panic_event(reader, String::from_utf8(string.to_vec()).unwrap()),
+                    _ => {
+                        return Err(ParseError::unexpected(
+                            reader.location(),
+                            EXPECTED,
+                            &Event::Start(start),
+                        ))
+                    }
                 },
-                Ok(ev) => panic_event(reader, ev),
+                Ok(Event::End(end)) if end.name().as_ref() == b"Step" => break,
+                event if reader.is_truncation(&event) => {
+                    self.incomplete = true;
+                    break;
+                }
+                Err(e) => return Err(ParseError::xml(reader.location(), e)),
+                Ok(ev) => return Err(ParseError::unexpected(reader.location(), EXPECTED, &ev)),
             }
         }
+        Ok(())
     }
-    /// true if the step does nothing or if it's marked as useless by `non_trivial_is_useless()`
-    pub fn does_nothing(&self) -> bool {
-        if let Some(before) = &self.before {
-            if let Some(after) = &self.after {
-                if before == after {
-                    return true;
-                }
+    /// builds the content of a `State` element, whose start has already been read
+    ///
+    /// Also returns whether the state is complete: the node is `None` or partial if the log ends
+    /// before the end of the state.
+    fn build_state<R: BufRead>(
+        reader: &mut EventReader<R>,
+    ) -> Result<(Option<PoincareNode>, bool), ParseError> {
+        let mut poincare_node = match PoincareNode::from_previous(reader)? {
+            Some(poincare_node) => poincare_node,
+            None => return Ok((None, false)),
+        };
+        if !poincare_node.build(reader)? {
+            return Ok((Some(poincare_node), false));
+        }
+        match reader.read_event() {
+            Ok(Event::End(end)) if end.name().as_ref() == b"State" => {
+                Ok((Some(poincare_node), true))
             }
+            event if reader.is_truncation(&event) => Ok((Some(poincare_node), false)),
+            Err(e) => Err(ParseError::xml(reader.location(), e)),
+            Ok(ev) => Err(ParseError::unexpected(
+                reader.location(),
+                "end of `State`",
+                &ev,
+            )),
+        }
+    }
+    /// true if the expression after the step is equal to the one before, according to `mode`
+    pub fn does_nothing(&self, mode: CompareMode) -> bool {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => before.equals(after, mode),
+            // by default suppose the step does something
+            _ => false,
         }
-        // by default suppose the step does something
-        false
     }
-    pub fn view(&self, long_form: bool) -> StepView {
+    pub(crate) fn ends_with_incomplete_substep(&self) -> bool {
+        matches!(self.parts.last(), Some(StepPart::Substep(substep)) if substep.incomplete)
+    }
+    /// number of steps inside the step, at any depth
+    pub fn count_substeps(&self) -> usize {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                StepPart::Substep(substep) => 1 + substep.count_substeps(),
+                StepPart::State(..) => 0,
+            })
+            .sum()
+    }
+    pub fn view<'a>(&'a self, options: &'a ViewOptions) -> StepView<'a> {
         StepView {
             node: self,
-            long_form,
+            options,
+            depth: 0,
         }
     }
 }
+
+/// How the steps are displayed
+#[derive(Debug, Clone, Default)]
+pub struct ViewOptions {
+    /// display the nodes in the long form
+    pub long_form: bool,
+    /// display the matrices of each expression as boxes, below the expression
+    pub matrix_box: bool,
+    /// highlight in the result of the steps how it differs from the expression before the step
+    pub diff: bool,
+    /// summarize on one line the steps inside more than `max_depth` steps
+    pub max_depth: Option<usize>,
+    /// patterns of the names of the steps displayed without their substeps and states, only
+    /// with the expressions before and after them
+    pub fold: Vec<NamePattern>,
+    /// draw the expressions on several lines, with fractions, exponents and radicals in 2D
+    pub two_d: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct StepView<'a> {
     node: &'a StepNode,
-    long_form: bool,
+    options: &'a ViewOptions,
+    /// number of steps containing the step
+    depth: usize,
+}
+impl<'a> StepView<'a> {
+    /// the first line of a step whose content is hidden, e.g. `/> name … (12 substeps hidden)`
+    fn write_summary(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hidden_str = match self.node.count_substeps() {
+            0 => String::new(),
+            1 => String::from(" (1 substep hidden)"),
+            count => format!(" ({} substeps hidden)", count),
+        };
+        let theme = theme();
+        write!(
+            f,
+            "{}",
+            format!("/> {} …{}", self.node.name, hidden_str)
+                .color(theme.step)
+                .bold()
+        )?;
+        if self.node.incomplete {
+            write!(f, " {}", "log truncated inside".color(theme.error).bold())?;
+        }
+        Ok(())
+    }
+    /// the 2D drawing of `node`, if the expressions are drawn in 2D and it takes several lines
+    fn picture(&self, node: &PoincareNode) -> Option<Picture> {
+        if !self.options.two_d || self.options.long_form {
+            return None;
+        }
+        Some(node.pretty_print_2d(0)).filter(|picture| picture.lines.len() > 1)
+    }
+    /// writes `prefix` and `output`, the display of `node`, followed by the boxes of its matrices
+    /// if they are displayed
+    ///
+    /// The lines of the boxes start with `box_prefix`, so that they are displayed in the step.
+    /// If `picture`, the 2D drawing of `node`, is given, it's written instead of `output`, with
+    /// `prefix` on its baseline, and the other lines start with a prefix as wide as `prefix`, so
+    /// that the drawing stays aligned in the substeps.
+    fn write_expression(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        prefix: &str,
+        node: &PoincareNode,
+        output: ColoredString,
+        picture: Option<Picture>,
+        box_prefix: &str,
+    ) -> fmt::Result {
+        let step_color = theme().step;
+        match picture {
+            Some(picture) => {
+                let width = visible_width(prefix);
+                let above_prefix = format!("{:<1$}", "|", width).color(step_color).bold();
+                let below_prefix = format!("{:<1$}", box_prefix.trim_end(), width)
+                    .color(step_color)
+                    .bold();
+                for (line_n, line) in picture.lines.iter().enumerate() {
+                    if line_n > 0 {
+                        writeln!(f)?;
+                    }
+                    match line_n.cmp(&picture.baseline) {
+                        Ordering::Less => write!(f, "{}", above_prefix)?,
+                        Ordering::Equal => write!(f, "{}", prefix)?,
+                        Ordering::Greater => write!(f, "{}", below_prefix)?,
+                    }
+                    write!(f, "{}", line)?;
+                }
+            }
+            None => write!(f, "{}{}", prefix, output)?,
+        }
+        if self.options.matrix_box {
+            for matrix in node.outermost_matrices() {
+                // matrices with wrong dimensions are already flagged in the expression
+                for line in matrix
+                    .matrix_box(0, self.options.long_form)
+                    .unwrap_or_default()
+                {
+                    write!(f, "\n{}{}", box_prefix.color(step_color).bold(), line)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 impl<'a> Display for StepView<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let begin_str = format!("/> {} \n", self.node.name).cyan().bold();
-        write!(f, "{}", begin_str)?;
+        if self
+            .options
+            .max_depth
+            .is_some_and(|max_depth| self.depth > max_depth)
+        {
+            return self.write_summary(f);
+        }
+        let folded = self
+            .options
+            .fold
+            .iter()
+            .any(|pattern| pattern.matches(&self.node.name));
+        let theme = theme();
+        if folded {
+            self.write_summary(f)?;
+            writeln!(f)?;
+        } else {
+            let begin_str = format!("/> {} \n", self.node.name).color(theme.step).bold();
+            write!(f, "{}", begin_str)?;
+        }
         if let Some(before) = &self.node.before {
-            writeln!(
-                f,
-                "{} {}",
-                "|".cyan().bold(),
-                before.pretty_print(0, self.long_form)
-            )?;
+            let prefix = format!("{} ", "|".color(theme.step).bold());
+            let output = before.pretty_print(0, self.options.long_form);
+            self.write_expression(f, &prefix, before, output, self.picture(before), "|   ")?;
+            writeln!(f)?;
         }
-        if self.node.parts.len() > 0 {
+        if !folded {
             for part in &self.node.parts {
                 match part {
                     StepPart::State(name, state) => {
@@ -113,31 +294,53 @@ impl<'a> Display for StepView<'a> {
                         } else {
                             String::new()
                         };
-                        writeln!(
-                            f,
-                            "{}{}{}",
-                            "|- ".cyan().bold(),
-                            state_prefix_str.cyan(),
-                            state.pretty_print(0, self.long_form)
-                        )?;
+                        let prefix = format!(
+                            "{}{}",
+                            "|- ".color(theme.step).bold(),
+                            state_prefix_str.color(theme.step)
+                        );
+                        let output = state.pretty_print(0, self.options.long_form);
+                        let picture = self.picture(state);
+                        self.write_expression(f, &prefix, state, output, picture, "|     ")?;
+                        writeln!(f)?;
                     }
                     StepPart::Substep(substep) => writeln!(
                         indented(f).with_str("|    "),
                         "{}",
-                        substep.view(self.long_form)
+                        StepView {
+                            node: substep,
+                            options: self.options,
+                            depth: self.depth + 1,
+                        }
                     )?,
                 }
             }
         }
-        if let Some(after) = &self.node.after {
-            write!(
+        if self.node.incomplete && !folded && !self.node.ends_with_incomplete_substep() {
+            // the step that was running when the log ended
+            writeln!(
                 f,
-                "{} {}",
-                "\\_".cyan().bold(),
-                after.pretty_print(0, self.long_form)
+                "{}{}",
+                "|~ ".color(theme.step).bold(),
+                "log truncated here".color(theme.error).bold()
             )?;
+        }
+        if let Some(after) = &self.node.after {
+            let prefix = format!("{} ", "\\_".color(theme.step).bold());
+            // the differences are highlighted in the short form
+            let (output, picture) = match &self.node.before {
+                Some(before) if self.options.diff => (
+                    TreeDiff::new(before, after).pretty_print(after, self.options.long_form),
+                    None,
+                ),
+                _ => (
+                    after.pretty_print(0, self.options.long_form),
+                    self.picture(after),
+                ),
+            };
+            self.write_expression(f, &prefix, after, output, picture, "   ")?;
         } else {
-            write!(f, "{}", "\\_".cyan().bold())?;
+            write!(f, "{}", "\\_".color(theme.step).bold())?;
         }
         Ok(())
     }
@@ -150,95 +353,356 @@ pub enum StepPart {
 }
 impl StepPart {
     /// removes recursively all step parts which are not useful, according to `is_useless`
+    ///
+    /// A step whose parts are all useless is useless too.
     pub fn remove_useless_recursive<F>(steps: &mut Vec<StepPart>, is_useless: F)
     where
         F: Fn(&StepPart) -> bool + Copy,
     {
-        fn is_useless_recursive<F>(part: &StepPart, is_useless_shallow: F) -> bool
-        where
-            F: Fn(&StepPart) -> bool + Copy,
-        {
-            if is_useless_shallow(part) {
-                return true;
+        Self::prune_recursive(steps, true, |part| {
+            if is_useless(part) {
+                FilterAction::Drop
+            } else {
+                FilterAction::Keep
             }
-            // if there are substeps, at least one must be useful
-            if let StepPart::Substep(step) = part {
-                if step.parts.len() > 0 {
-                    for part in &step.parts {
-                        if !is_useless_recursive(part, is_useless_shallow) {
-                            return false;
-                        }
-                    }
-                    return true; // all substeps were useless
+        });
+    }
+    /// drops or collapses recursively the substeps, according to `action`
+    ///
+    /// Unlike [`StepPart::remove_useless_recursive`], a step is kept when all its substeps are
+    /// removed.
+    pub fn filter_recursive<F>(parts: &mut Vec<StepPart>, action: F)
+    where
+        F: Fn(&StepNode) -> FilterAction + Copy,
+    {
+        Self::prune_recursive(parts, false, |part| match part {
+            StepPart::State(..) => FilterAction::Keep,
+            StepPart::Substep(step) => action(step),
+        });
+    }
+    /// the steps of `parts`, the states between them being kept in steps called `name`
+    ///
+    /// This is used for the parts left by a collapsed top-level step called `name`, as its
+    /// states can't be displayed without a step.
+    pub fn into_steps(parts: Vec<StepPart>, name: &str) -> Vec<StepNode> {
+        let mut steps = Vec::new();
+        let mut states = Vec::new();
+        let wrap = |states: &mut Vec<StepPart>, steps: &mut Vec<StepNode>| {
+            if !states.is_empty() {
+                steps.push(StepNode {
+                    before: None,
+                    after: None,
+                    parts: mem::take(states),
+                    name: String::from(name),
+                    incomplete: false,
+                });
+            }
+        };
+        for part in parts {
+            match part {
+                StepPart::State(..) => states.push(part),
+                StepPart::Substep(step) => {
+                    wrap(&mut states, &mut steps);
+                    steps.push(step);
                 }
             }
-            false // assume a step is useful by default
         }
-        let mut parts_to_remove: Vec<usize> = Vec::new();
-        for (n, part) in steps.iter_mut().enumerate() {
-            if is_useless_recursive(part, is_useless) {
-                parts_to_remove.push(n);
-            } else {
-                match part {
-                    StepPart::State(..) => (), // keep a useful state
-                    StepPart::Substep(step) => {
-                        Self::remove_useless_recursive(&mut step.parts, is_useless)
+        wrap(&mut states, &mut steps);
+        steps
+    }
+    /// applies `action` to the parts, from the outermost ones, removing the steps whose parts
+    /// have all been removed if `drop_emptied` is true
+    fn prune_recursive<F>(parts: &mut Vec<StepPart>, drop_emptied: bool, action: F)
+    where
+        F: Fn(&StepPart) -> FilterAction + Copy,
+    {
+        let mut pruned_parts = Vec::with_capacity(parts.len());
+        for part in parts.drain(..) {
+            let part_action = action(&part);
+            let mut step = match part {
+                StepPart::State(..) => {
+                    if part_action != FilterAction::Drop {
+                        pruned_parts.push(part);
                     }
-                };
+                    continue;
+                }
+                StepPart::Substep(step) => step,
+            };
+            // keep showing where a truncated log stops
+            if part_action == FilterAction::Drop && !step.incomplete {
+                continue;
+            }
+            let had_parts = !step.parts.is_empty();
+            Self::prune_recursive(&mut step.parts, drop_emptied, action);
+            match part_action {
+                _ if step.incomplete => pruned_parts.push(StepPart::Substep(step)),
+                FilterAction::Keep if drop_emptied && had_parts && step.parts.is_empty() => (),
+                FilterAction::Keep => pruned_parts.push(StepPart::Substep(step)),
+                FilterAction::Drop => (),
+                FilterAction::Collapse => pruned_parts.append(&mut step.parts),
             }
         }
-        // remove elements from the last one so that indexes don't change in the mean time
-        while !parts_to_remove.is_empty() {
-            let n = parts_to_remove.pop().unwrap();
-            steps.remove(n);
-        }
+        *parts = pruned_parts;
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct StepTypeMask {
-    pub based_integer_to_rational: bool,
-    pub to_undef: bool,
+/// What to do with a step when filtering the steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    Keep,
+    /// remove the step with its substeps
+    Drop,
+    /// replace the step by its substeps and states
+    Collapse,
 }
-impl StepTypeMask {
-    pub fn step_is_either(&self, step: &StepNode) -> bool {
-        if self.based_integer_to_rational {
-            if step.parts.len() == 0 {
-                if let Some(before) = &step.before {
-                    if let Some(after) = &step.after {
-                        if before.name == "BasedInteger" && after.name == "Rational" {
-                            return true;
-                        }
-                    }
-                }
-            }
-        }
-        if self.to_undef {
-            if let Some(result) = &step.after {
-                fn node_is_undef(node: &PoincareNode) -> bool {
-                    if node.name == "Undefined" {
-                        return true;
-                    }
-                    for child in &node.children {
-                        if node_is_undef(child) {
-                            return true;
-                        }
-                    }
-                    false
-                }
-                if node_is_undef(result) {
-                    return true;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+    use crate::poincare::strip_colors;
+    use crate::theme::ColorOverride;
+
+    const TRUNCATED_LOG: &str = r#"<Step name="simplify">
+  <State name="before"><Symbol id="1" name="x"></Symbol></State>
+  <Step name="shallowReduce">
+    <State name="before"><Power id="2"><Addition id="3"><Symbol id="4" name="a"></Symbol><Symbol id="5" name="b"></Symbol></Addition>"#;
+
+    #[test]
+    fn reads_truncated_steps_as_far_as_possible() {
+        let steps = parse_log(TRUNCATED_LOG).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].incomplete);
+        assert!(steps[0].ends_with_incomplete_substep());
+        let StepPart::Substep(substep) = &steps[0].parts[0] else {
+            panic!("expected a substep");
+        };
+        assert!(substep.incomplete);
+        assert!(substep.after.is_none());
+        let power = substep.before.as_ref().unwrap();
+        assert!(power.incomplete);
+        assert!(!power.children[0].incomplete);
+    }
+
+    #[test]
+    fn colors_the_steps_with_the_theme() {
+        let steps = parse_log(TRUNCATED_LOG).unwrap();
+        let _colors = ColorOverride::set(true);
+        let theme = theme();
+        let view = steps[0].view(&ViewOptions::default()).to_string();
+        let begin_str = "/> simplify \n".color(theme.step).bold().to_string();
+        assert!(view.starts_with(&begin_str));
+        let truncated_str = format!(
+            "{}{}",
+            "|~ ".color(theme.step).bold(),
+            "log truncated here".color(theme.error).bold()
+        );
+        assert!(view.contains(&truncated_str));
+        let options = ViewOptions {
+            max_depth: Some(0),
+            ..ViewOptions::default()
+        };
+        let summary = steps[0].view(&options).to_string();
+        assert!(summary.contains(&"log truncated inside".color(theme.error).bold().to_string()));
+    }
+
+    #[test]
+    fn marks_the_missing_children_of_truncated_nodes() {
+        let steps = parse_log(TRUNCATED_LOG).unwrap();
+        let StepPart::Substep(substep) = &steps[0].parts[0] else {
+            panic!("expected a substep");
+        };
+        let power = substep.before.as_ref().unwrap();
+        assert_eq!(
+            strip_colors(&power.pretty_print(0, false).to_string()),
+            "(a + b) ^ \u{2026}"
+        );
+        let view = strip_colors(&steps[0].view(&ViewOptions::default()).to_string());
+        assert!(view.contains("|~ log truncated here"));
+    }
+
+    #[test]
+    fn marks_the_missing_children_of_nodes_cut_inside_a_child() {
+        let log = r#"<Step name="s"><State name="before"><Power id="1"><Addition id="2"><Symbol id="3" name="a"></Symbol>"#;
+        let steps = parse_log(log).unwrap();
+        let power = steps[0].before.as_ref().unwrap();
+        assert_eq!(
+            strip_colors(&power.pretty_print(0, false).to_string()),
+            "(a + \u{2026}) ^ \u{2026}"
+        );
+    }
+
+    /// a step called `name` with the substeps called `substeps`, each of them being empty
+    fn step(name: &str, substeps: &[&str]) -> StepPart {
+        let substeps: Vec<String> = substeps
+            .iter()
+            .map(|substep| format!(r#"<Step name="{}"></Step>"#, substep))
+            .collect();
+        let log = format!(r#"<Step name="{}">{}</Step>"#, name, substeps.concat());
+        StepPart::Substep(parse_log(&log).unwrap().remove(0))
+    }
+
+    /// the names of the steps of `parts`, with their substeps in parentheses
+    fn names(parts: &[StepPart]) -> Vec<String> {
+        parts
+            .iter()
+            .filter_map(|part| match part {
+                StepPart::Substep(step) if step.parts.is_empty() => Some(step.name.clone()),
+                StepPart::Substep(step) => {
+                    Some(format!("{}({})", step.name, names(&step.parts).join(", ")))
                 }
-            }
-        }
-        false
+                StepPart::State(..) => None,
+            })
+            .collect()
     }
-}
-impl Default for StepTypeMask {
-    fn default() -> Self {
-        Self {
-            based_integer_to_rational: false,
-            to_undef: false,
-        }
+
+    fn named(name: &'static str) -> impl Fn(&StepPart) -> bool + Copy {
+        move |part| matches!(part, StepPart::Substep(step) if step.name == name)
+    }
+
+    #[test]
+    fn removes_the_steps_whose_substeps_are_all_useless() {
+        let mut parts = vec![
+            step("a", &["useless", "useless"]),
+            step("b", &["useless", "c"]),
+        ];
+        StepPart::remove_useless_recursive(&mut parts, named("useless"));
+        assert_eq!(names(&parts), ["b(c)"]);
+    }
+
+    #[test]
+    fn keeps_the_filtered_steps_whose_substeps_are_all_dropped() {
+        let mut parts = vec![
+            step("a", &["dropped", "dropped"]),
+            step("b", &["dropped", "c"]),
+        ];
+        StepPart::filter_recursive(&mut parts, |step| match step.name.as_str() {
+            "dropped" => FilterAction::Drop,
+            _ => FilterAction::Keep,
+        });
+        assert_eq!(names(&parts), ["a", "b(c)"]);
+    }
+
+    #[test]
+    fn replaces_collapsed_steps_by_their_substeps() {
+        let mut parts = vec![step("a", &["c", "d"]), step("b", &[])];
+        StepPart::filter_recursive(&mut parts, |step| match step.name.as_str() {
+            "a" => FilterAction::Collapse,
+            _ => FilterAction::Keep,
+        });
+        assert_eq!(names(&parts), ["c", "d", "b"]);
+    }
+
+    #[test]
+    fn keeps_the_states_of_a_collapsed_top_level_step() {
+        let log = r#"<Step name="simplify">
+  <State name="before"><Symbol id="1" name="x"></Symbol></State>
+  <Step name="reduce"></Step>
+  <State name="expanded"><Symbol id="2" name="y"></Symbol></State>
+  <State name="after"><Symbol id="2" name="y"></Symbol></State>
+</Step>"#;
+        let step = parse_log(log).unwrap().remove(0);
+        let mut parts = vec![StepPart::Substep(step)];
+        StepPart::filter_recursive(&mut parts, |step| match step.name.as_str() {
+            "simplify" => FilterAction::Collapse,
+            _ => FilterAction::Keep,
+        });
+        let steps = StepPart::into_steps(parts, "simplify");
+        let names: Vec<&str> = steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(names, ["reduce", "simplify"]);
+        assert!(matches!(
+            &steps[1].parts[..],
+            [StepPart::State(Some(name), _)] if name == "expanded"
+        ));
+    }
+
+    const NESTED_LOG: &str = r#"<Step name="simplify">
+  <State name="before"><Symbol id="1" name="a"></Symbol></State>
+  <Step name="reduce">
+    <State name="before"><Symbol id="1" name="a"></Symbol></State>
+    <Step name="shallowReduce">
+      <State name="before"><Symbol id="1" name="a"></Symbol></State>
+      <Step name="inner">
+        <State name="before"><Symbol id="1" name="a"></Symbol></State>
+        <State name="after"><Symbol id="2" name="b"></Symbol></State>
+      </Step>
+      <State name="after"><Symbol id="2" name="b"></Symbol></State>
+    </Step>
+    <State name="expanded"><Symbol id="2" name="b"></Symbol></State>
+    <State name="after"><Symbol id="2" name="b"></Symbol></State>
+  </Step>
+  <State name="after"><Symbol id="2" name="b"></Symbol></State>
+</Step>"#;
+
+    fn nested_view(options: &ViewOptions) -> String {
+        let step = parse_log(NESTED_LOG).unwrap().remove(0);
+        strip_colors(&step.view(options).to_string())
+    }
+
+    #[test]
+    fn summarizes_the_steps_deeper_than_the_maximum_depth() {
+        let options = ViewOptions {
+            max_depth: Some(1),
+            ..ViewOptions::default()
+        };
+        assert_eq!(
+            nested_view(&options),
+            [
+                "/> simplify ",
+                "| a",
+                "|    /> reduce ",
+                "|    | a",
+                "|    |    /> shallowReduce … (1 substep hidden)",
+                "|    |- expanded: b",
+                "|    \\_ b",
+                "\\_ b",
+            ]
+            .join("\n")
+        );
+        let options = ViewOptions {
+            max_depth: Some(0),
+            ..ViewOptions::default()
+        };
+        assert_eq!(
+            nested_view(&options),
+            [
+                "/> simplify ",
+                "| a",
+                "|    /> reduce … (2 substeps hidden)",
+                "\\_ b",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn folds_the_steps_by_name() {
+        let options = ViewOptions {
+            fold: vec![NamePattern::new("red*").unwrap()],
+            ..ViewOptions::default()
+        };
+        assert_eq!(
+            nested_view(&options),
+            [
+                "/> simplify ",
+                "| a",
+                "|    /> reduce … (2 substeps hidden)",
+                "|    | a",
+                "|    \\_ b",
+                "\\_ b",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn marks_the_summaries_of_truncated_steps() {
+        let options = ViewOptions {
+            max_depth: Some(0),
+            ..ViewOptions::default()
+        };
+        let step = parse_log(TRUNCATED_LOG).unwrap().remove(0);
+        let view = strip_colors(&step.view(&options).to_string());
+        assert!(view.contains("/> shallowReduce … log truncated inside"));
     }
 }
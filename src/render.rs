@@ -0,0 +1,1106 @@
+//! Short form of the Poincare nodes.
+//!
+//! Each node name is associated with a [`NodeRenderer`] in a [`RenderRegistry`]. The registry
+//! used by [`PoincareNode::pretty_print`] contains built-in renderers for the known nodes, and can
+//! be extended with rules read from a TOML or JSON file, see [`RenderRegistry::load_rules`].
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use colored::*;
+use serde::Deserialize;
+
+use crate::layout;
+use crate::poincare::{PoincareAttributes, PoincareNode};
+use crate::theme::theme;
+
+/// Precedence levels of the short form, from the loosest to the tightest
+pub struct Precedence;
+impl Precedence {
+    pub const STORE: u8 = 1;
+    pub const RELATION: u8 = 2;
+    pub const ADDITIVE: u8 = 3;
+    pub const OPPOSITE: u8 = 4;
+    pub const MULTIPLICATIVE: u8 = 5;
+    pub const POWER: u8 = 6;
+    pub const POSTFIX: u8 = 7;
+    pub const ATOM: u8 = u8::MAX;
+
+    fn from_name(name: &str) -> Option<u8> {
+        Some(match name {
+            "store" => Self::STORE,
+            "relation" => Self::RELATION,
+            "additive" => Self::ADDITIVE,
+            "opposite" => Self::OPPOSITE,
+            "multiplicative" => Self::MULTIPLICATIVE,
+            "power" => Self::POWER,
+            "postfix" => Self::POSTFIX,
+            "atom" => Self::ATOM,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`
+    Right,
+    /// the node can have any number of children, so `a + b + c` is one node with three children
+    Nary,
+}
+
+/// The short form of a child, as given to the renderer of its parent
+pub struct Operand<'a> {
+    /// the child, or its parent for the `…` standing for the missing children of an incomplete
+    /// node
+    pub node: &'a PoincareNode,
+    pub text: String,
+    /// how tightly the short form of the child binds its parts together
+    pub precedence: u8,
+    /// the child is an n-ary operator
+    pub nary: bool,
+}
+impl<'a> Operand<'a> {
+    fn parenthesized_if(&self, parentheses: bool) -> String {
+        if parentheses {
+            format!("({})", self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Renders one kind of node in the short form
+pub trait NodeRenderer: Send + Sync {
+    /// short form of `node`, or `None` to display its long form instead
+    fn render(&self, node: &PoincareNode, children: &[Operand]) -> Option<String>;
+    /// how tightly the short form of `node` binds its parts together
+    fn precedence(&self, _node: &PoincareNode) -> u8 {
+        Precedence::ATOM
+    }
+    fn associativity(&self) -> Option<Associativity> {
+        None
+    }
+}
+
+/// Displays the attributes of the node, e.g. for numbers and symbols, followed by its children
+/// as arguments if it has any, e.g. for `f(x)`
+pub struct AttributesRenderer;
+impl NodeRenderer for AttributesRenderer {
+    fn render(&self, node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        let attributes = node.attributes.as_ref()?.pretty_print();
+        if children.is_empty() {
+            return Some(attributes);
+        }
+        let arguments: Vec<&str> = children.iter().map(|child| child.text.as_str()).collect();
+        Some(format!("{}({})", attributes, arguments.join(", ")))
+    }
+    fn precedence(&self, node: &PoincareNode) -> u8 {
+        // numbers displayed with a sign or as a fraction behave like the corresponding operator
+        match &node.attributes {
+            Some(PoincareAttributes::Rational { negative, .. }) if negative != "0" => {
+                Precedence::OPPOSITE
+            }
+            Some(PoincareAttributes::Rational { .. }) => Precedence::MULTIPLICATIVE,
+            Some(PoincareAttributes::Decimal { negative, .. }) if negative != "0" => {
+                Precedence::OPPOSITE
+            }
+            Some(PoincareAttributes::Decimal { .. }) => Precedence::MULTIPLICATIVE,
+            Some(attributes) if attributes.pretty_print().starts_with('-') => Precedence::OPPOSITE,
+            _ => Precedence::ATOM,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notation {
+    Infix,
+    Prefix,
+    Postfix,
+}
+
+/// Displays the node as an operator, adding parentheses around operands only when they would be
+/// read differently without them
+pub struct OperatorRenderer {
+    pub symbol: String,
+    pub notation: Notation,
+    pub precedence: u8,
+    pub associativity: Associativity,
+}
+impl OperatorRenderer {
+    pub fn new(
+        symbol: &str,
+        notation: Notation,
+        precedence: u8,
+        associativity: Associativity,
+    ) -> Self {
+        Self {
+            symbol: String::from(symbol),
+            notation,
+            precedence,
+            associativity,
+        }
+    }
+    /// true if the `child_n`th operand must be put in parentheses to be read unambiguously
+    fn needs_parentheses(&self, child_n: usize, child: &Operand) -> bool {
+        if child.precedence != self.precedence {
+            return child.precedence < self.precedence;
+        }
+        match (self.notation, self.associativity) {
+            // `--x` or `x!!` would be read as another operator
+            (Notation::Prefix, _) | (Notation::Postfix, _) => true,
+            // keep the nesting of n-ary nodes visible
+            (_, Associativity::Nary) => child_n > 0 || child.nary,
+            (_, Associativity::Left) => child_n > 0,
+            (_, Associativity::Right) => child_n == 0,
+        }
+    }
+}
+impl NodeRenderer for OperatorRenderer {
+    fn render(&self, _node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        let operand = |child_n: usize| {
+            let child = &children[child_n];
+            child.parenthesized_if(self.needs_parentheses(child_n, child))
+        };
+        match self.notation {
+            Notation::Infix if !children.is_empty() => {
+                let operands: Vec<String> = (0..children.len()).map(operand).collect();
+                Some(operands.join(&format!(" {} ", self.symbol)))
+            }
+            // a unary operator only has one child, except in a truncated log
+            Notation::Prefix if children.len() == 1 => {
+                Some(format!("{}{}", self.symbol, operand(0)))
+            }
+            Notation::Postfix if children.len() == 1 => {
+                Some(format!("{}{}", operand(0), self.symbol))
+            }
+            _ => None,
+        }
+    }
+    fn precedence(&self, _node: &PoincareNode) -> u8 {
+        self.precedence
+    }
+    fn associativity(&self) -> Option<Associativity> {
+        Some(self.associativity)
+    }
+}
+
+/// Displays a comparison with the operator given in its attributes, e.g. `a < b`
+pub struct ComparisonRenderer;
+impl NodeRenderer for ComparisonRenderer {
+    fn render(&self, node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        let operator = match &node.attributes {
+            Some(PoincareAttributes::Comparison { operator }) => operator.as_str(),
+            // the operator isn't known
+            _ => "?",
+        };
+        OperatorRenderer::new(
+            operator,
+            Notation::Infix,
+            Precedence::RELATION,
+            Associativity::Left,
+        )
+        .render(node, children)
+    }
+    fn precedence(&self, _node: &PoincareNode) -> u8 {
+        Precedence::RELATION
+    }
+}
+
+/// Displays the node as a function applied to its children, e.g. `cos(x)`
+pub struct FunctionRenderer {
+    pub name: String,
+}
+impl FunctionRenderer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+        }
+    }
+}
+impl NodeRenderer for FunctionRenderer {
+    fn render(&self, _node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        let arguments: Vec<&str> = children.iter().map(|child| child.text.as_str()).collect();
+        Some(format!("{}({})", self.name, arguments.join(", ")))
+    }
+}
+
+/// Always displays the same text, e.g. for `Undefined`
+pub struct NameRenderer {
+    pub name: String,
+}
+impl NameRenderer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+        }
+    }
+}
+impl NodeRenderer for NameRenderer {
+    fn render(&self, _node: &PoincareNode, _children: &[Operand]) -> Option<String> {
+        Some(self.name.clone())
+    }
+}
+
+/// Lays the children of a matrix out in rows, e.g. `[[a, b], [c, d]]`
+///
+/// When the number of children doesn't match the dimensions of the matrix, the children are
+/// laid out as far as possible and followed by a warning, e.g. `[[a, b], [c]] (!3 children for
+/// 2x2)`.
+pub struct MatrixRenderer;
+impl NodeRenderer for MatrixRenderer {
+    fn render(&self, node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        let texts: Vec<&str> = children.iter().map(|child| child.text.as_str()).collect();
+        let (rows, columns) = match node.matrix_dimensions() {
+            Some(dimensions) => dimensions,
+            None => {
+                let warning = "(!unknown dimensions)".color(theme().error).bold();
+                return Some(format!("[{}] {}", texts.join(", "), warning));
+            }
+        };
+        let laid_out = if columns == 0 {
+            format!("[{}]", texts.join(", "))
+        } else {
+            let rows_output: Vec<String> = texts
+                .chunks(columns)
+                .map(|row| format!("[{}]", row.join(", ")))
+                .collect();
+            format!("[{}]", rows_output.join(", "))
+        };
+        // the `…` of an incomplete matrix isn't a child
+        if rows * columns == node.children.len() {
+            Some(laid_out)
+        } else {
+            let warning = format!(
+                "(!{} children for {}x{})",
+                node.children.len(),
+                rows,
+                columns
+            );
+            Some(format!(
+                "{} {}",
+                laid_out,
+                warning.color(theme().error).bold()
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Child(usize),
+    /// all the children, separated by commas
+    AllChildren,
+}
+
+/// Displays the node by replacing `{0}`, `{1}`... by its children in a template, e.g. `|{0}|`.
+///
+/// `{*}` is replaced by all the children separated by commas, and `{{` and `}}` are literal braces.
+/// A child at the very start or end of the template is put in parentheses when its precedence is
+/// lower than the precedence of the template.
+pub struct TemplateRenderer {
+    parts: Vec<TemplatePart>,
+    precedence: u8,
+}
+impl TemplateRenderer {
+    pub fn new(template: &str, precedence: u8) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!("unclosed `{{` in `{}`", template)),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(match placeholder.as_str() {
+                        "*" => TemplatePart::AllChildren,
+                        n => TemplatePart::Child(n.parse().map_err(|_| {
+                            format!("invalid placeholder `{{{}}}` in `{}`", n, template)
+                        })?),
+                    });
+                }
+                '}' => return Err(format!("unmatched `}}` in `{}`", template)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+        Ok(Self { parts, precedence })
+    }
+}
+impl NodeRenderer for TemplateRenderer {
+    fn render(&self, _node: &PoincareNode, children: &[Operand]) -> Option<String> {
+        let mut output = String::new();
+        for (part_n, part) in self.parts.iter().enumerate() {
+            match part {
+                TemplatePart::Text(text) => output.push_str(text),
+                TemplatePart::Child(child_n) => {
+                    // the node doesn't have the expected children, e.g. in a truncated log
+                    let child = children.get(*child_n)?;
+                    let exposed = part_n == 0 || part_n == self.parts.len() - 1;
+                    output.push_str(
+                        &child.parenthesized_if(exposed && child.precedence < self.precedence),
+                    );
+                }
+                TemplatePart::AllChildren => {
+                    let texts: Vec<&str> =
+                        children.iter().map(|child| child.text.as_str()).collect();
+                    output.push_str(&texts.join(", "));
+                }
+            }
+        }
+        Some(output)
+    }
+    fn precedence(&self, _node: &PoincareNode) -> u8 {
+        self.precedence
+    }
+}
+
+/// Renderers of the short form, by node name
+pub struct RenderRegistry {
+    renderers: HashMap<String, Box<dyn NodeRenderer>>,
+}
+impl RenderRegistry {
+    /// a registry without any renderer, so that all nodes are displayed in the long form
+    pub fn empty() -> Self {
+        Self {
+            renderers: HashMap::new(),
+        }
+    }
+    /// a registry with the renderers of all the known nodes
+    pub fn builtin() -> Self {
+        use Associativity::*;
+        use Notation::*;
+        let mut registry = Self::empty();
+        for name in [
+            "Symbol",
+            "SymbolAbstract",
+            "Sequence",
+            "Function",
+            "Constant",
+            "BasedInteger",
+            "Decimal",
+            "Float",
+            "Integer",
+            "Rational",
+            "Infinity",
+            "Boolean",
+            "Unit",
+            "CodePointLayout",
+        ] {
+            registry.register(name, AttributesRenderer);
+        }
+        for (name, symbol, notation, precedence, associativity) in [
+            ("Store", "->", Infix, Precedence::STORE, Left),
+            ("UnitConvert", "->", Infix, Precedence::STORE, Left),
+            ("Equal", "=", Infix, Precedence::RELATION, Left),
+            ("Addition", "+", Infix, Precedence::ADDITIVE, Nary),
+            ("Subtraction", "-", Infix, Precedence::ADDITIVE, Left),
+            ("Opposite", "-", Prefix, Precedence::OPPOSITE, Right),
+            (
+                "Multiplication",
+                "*",
+                Infix,
+                Precedence::MULTIPLICATIVE,
+                Nary,
+            ),
+            ("Division", "/", Infix, Precedence::MULTIPLICATIVE, Left),
+            ("Power", "^", Infix, Precedence::POWER, Right),
+            ("Factorial", "!", Postfix, Precedence::POSTFIX, Left),
+            ("PercentSimple", "%", Postfix, Precedence::POSTFIX, Left),
+            (
+                "LogicalOperatorNot",
+                "not ",
+                Prefix,
+                Precedence::OPPOSITE,
+                Right,
+            ),
+        ] {
+            registry.register(
+                name,
+                OperatorRenderer::new(symbol, notation, precedence, associativity),
+            );
+        }
+        for (name, function) in [
+            ("AbsoluteValue", "abs"),
+            ("ArcCosine", "acos"),
+            ("ArcSine", "asin"),
+            ("ArcTangent", "atan"),
+            ("BinomCDF", "bCDF"),
+            ("BinomPDF", "bPDF"),
+            ("Ceiling", "ceil"),
+            ("Conjugate", "conj"),
+            ("Cosine", "cos"),
+            ("Derivative", "der"),
+            ("Floor", "floor"),
+            ("FracPart", "frac"),
+            ("GreatCommonDivisor", "gcd"),
+            ("HyperbolicArcCosine", "hacos"),
+            ("HyperbolicArcSine", "hasin"),
+            ("HyperbolicArcTangent", "hatan"),
+            ("HyperbolicCosine", "hcos"),
+            ("HyperbolicSine", "hsin"),
+            ("HyperbolicTangent", "htan"),
+            ("ImaginaryPart", "imag"),
+            ("LeastCommonMultiple", "lcm"),
+            ("Integral", "int"),
+            ("Logarithm", "log"),
+            ("Randint", "randint"),
+            ("Random", "rand"),
+            ("RealPart", "real"),
+            ("Round", "round"),
+            ("SignFunction", "sign"),
+            ("Sine", "sin"),
+            ("Tangent", "tan"),
+            ("SquareRoot", "sqrt"),
+            ("NaperianLogarithm", "ln"),
+            ("ArcCosecant", "acsc"),
+            ("ArcCotangent", "acot"),
+            ("ArcSecant", "asec"),
+            ("Cosecant", "csc"),
+            ("Cotangent", "cot"),
+            ("Secant", "sec"),
+            ("BinomialCoefficient", "binomial"),
+            ("PermuteCoefficient", "permute"),
+            ("NthRoot", "root"),
+            ("ComplexArgument", "arg"),
+            ("DivisionQuotient", "quo"),
+            ("DivisionRemainder", "rem"),
+            ("Factor", "factor"),
+            ("Sum", "sum"),
+            ("Product", "product"),
+            ("ConfidenceInterval", "confidence"),
+            ("PredictionInterval", "prediction95"),
+            ("SimplePredictionInterval", "prediction"),
+            ("InvBinom", "invbinom"),
+            ("InvNorm", "invnorm"),
+            ("NormCDF", "normcdf"),
+            ("NormCDF2", "normcdf2"),
+            ("NormPDF", "normpdf"),
+            ("Determinant", "det"),
+            ("MatrixDimension", "dim"),
+            ("MatrixIdentity", "identity"),
+            ("MatrixInverse", "inverse"),
+            ("MatrixTrace", "trace"),
+            ("MatrixTranspose", "transpose"),
+            ("MatrixRowEchelonForm", "ref"),
+            ("MatrixReducedRowEchelonForm", "rref"),
+            ("VectorCross", "cross"),
+            ("VectorDot", "dot"),
+            ("VectorNorm", "norm"),
+            ("Dependency", "dep"),
+            ("ListMaximum", "max"),
+            ("ListMean", "mean"),
+            ("ListMedian", "med"),
+            ("ListMinimum", "min"),
+            ("ListProduct", "prod"),
+            ("ListSampleStandardDeviation", "samplestddev"),
+            ("ListSequence", "sequence"),
+            ("ListSort", "sort"),
+            ("ListStandardDeviation", "stddev"),
+            ("ListSum", "sum"),
+            ("ListVariance", "var"),
+            ("PiecewiseOperator", "piecewise"),
+        ] {
+            registry.register(name, FunctionRenderer::new(function));
+        }
+        for (name, plain_name) in [
+            ("Undefined", "undef"),
+            ("Nonreal", "nonreal"),
+            ("EmptyExpression", "\u{25a1}"),
+            ("Ghost", "ghost"),
+        ] {
+            registry.register(name, NameRenderer::new(plain_name));
+        }
+        registry.register("Comparison", ComparisonRenderer);
+        registry.register("Matrix", MatrixRenderer);
+        layout::register_builtin(&mut registry);
+        for (name, template) in [
+            // display {} for ParenthesisNode to differentiate it from other parentheses
+            ("Parenthesis", "{{{0}}}"),
+            ("List", "{{{*}}}"),
+            ("ListElement", "{0}({1})"),
+            ("ListSlice", "{0}({1}, {2})"),
+            ("Point", "({0}, {1})"),
+            ("PercentAddition", "{0} \u{2197} {1}%"),
+            ("MixedFraction", "{0} {1}"),
+        ] {
+            registry.register(
+                name,
+                TemplateRenderer::new(template, Precedence::ATOM).unwrap(),
+            );
+        }
+        registry
+    }
+    /// sets the renderer of the nodes called `name`, replacing the previous one
+    pub fn register<R: NodeRenderer + 'static>(&mut self, name: &str, renderer: R) {
+        self.renderers
+            .insert(String::from(name), Box::new(renderer));
+    }
+    pub fn get(&self, name: &str) -> Option<&dyn NodeRenderer> {
+        self.renderers.get(name).map(|renderer| renderer.as_ref())
+    }
+    /// short form of `node`, or `None` if it must be displayed in the long form
+    ///
+    /// `children` are the short forms of the children of `node`. The children missing from an
+    /// incomplete node are displayed as a last `…` operand, e.g. `(a + b) ^ …`.
+    pub fn render(&self, node: &PoincareNode, children: Vec<String>) -> Option<String> {
+        let renderer = self.get(&node.name)?;
+        let mut operands: Vec<Operand> = node
+            .children
+            .iter()
+            .zip(children)
+            .map(|(child, text)| Operand {
+                node: child,
+                text,
+                precedence: self.precedence(child),
+                nary: self
+                    .get(&child.name)
+                    .and_then(|renderer| renderer.associativity())
+                    == Some(Associativity::Nary),
+            })
+            .collect();
+        if node.incomplete {
+            operands.push(Operand {
+                node,
+                text: String::from("\u{2026}"),
+                precedence: Precedence::ATOM,
+                nary: false,
+            });
+        }
+        renderer.render(node, &operands)
+    }
+    /// how tightly the short form of `node` binds its parts together
+    pub fn precedence(&self, node: &PoincareNode) -> u8 {
+        match self.get(&node.name) {
+            Some(renderer) => renderer.precedence(node),
+            None => Precedence::ATOM,
+        }
+    }
+    /// adds the rules of a TOML file, or of a JSON file if its extension is `.json`
+    ///
+    /// The file maps node names to rules, e.g. in TOML:
+    /// ```toml
+    /// [Factorial]
+    /// template = "{0}!"
+    /// precedence = "postfix"
+    ///
+    /// [Modulo]
+    /// infix = "mod"
+    /// precedence = "multiplicative"
+    /// associativity = "left"
+    /// ```
+    pub fn load_rules(&mut self, path: &Path) -> Result<(), RulesError> {
+        let content = read_to_string(path).map_err(|e| RulesError::Io(e.to_string()))?;
+        let rules: HashMap<String, Rule> = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::from_str(&content).map_err(|e| RulesError::Syntax(e.to_string()))?
+        } else {
+            toml::from_str(&content).map_err(|e| RulesError::Syntax(e.to_string()))?
+        };
+        for (name, rule) in rules {
+            let renderer = rule
+                .into_renderer()
+                .map_err(|message| RulesError::Invalid {
+                    node: name.clone(),
+                    message,
+                })?;
+            self.renderers.insert(name, renderer);
+        }
+        Ok(())
+    }
+    /// makes this registry the one used by [`PoincareNode::pretty_print`]
+    ///
+    /// This can only be done once, before anything is displayed.
+    pub fn install(self) -> Result<(), Self> {
+        REGISTRY.set(self)
+    }
+}
+
+static REGISTRY: OnceLock<RenderRegistry> = OnceLock::new();
+
+/// the registry used by [`PoincareNode::pretty_print`]
+pub fn registry() -> &'static RenderRegistry {
+    REGISTRY.get_or_init(RenderRegistry::builtin)
+}
+
+/// A rule of a rules file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    infix: Option<String>,
+    prefix: Option<String>,
+    postfix: Option<String>,
+    template: Option<String>,
+    function: Option<String>,
+    name: Option<String>,
+    /// a level name, like `"multiplicative"`, or a number
+    precedence: Option<RulePrecedence>,
+    /// `"left"`, `"right"` or `"nary"`
+    associativity: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RulePrecedence {
+    Level(u8),
+    Name(String),
+}
+
+impl Rule {
+    fn into_renderer(self) -> Result<Box<dyn NodeRenderer>, String> {
+        let precedence = match &self.precedence {
+            None => None,
+            Some(RulePrecedence::Level(level)) => Some(*level),
+            Some(RulePrecedence::Name(name)) => Some(
+                Precedence::from_name(name)
+                    .ok_or_else(|| format!("unknown precedence `{}`", name))?,
+            ),
+        };
+        let associativity = match self.associativity.as_deref() {
+            None | Some("left") => Associativity::Left,
+            Some("right") => Associativity::Right,
+            Some("nary") => Associativity::Nary,
+            Some(other) => return Err(format!("unknown associativity `{}`", other)),
+        };
+        let operator = |symbol: String, notation, default_precedence| -> Box<dyn NodeRenderer> {
+            Box::new(OperatorRenderer::new(
+                &symbol,
+                notation,
+                precedence.unwrap_or(default_precedence),
+                associativity,
+            ))
+        };
+        let mut renderers: Vec<Box<dyn NodeRenderer>> = Vec::new();
+        if let Some(symbol) = self.infix {
+            renderers.push(operator(
+                symbol,
+                Notation::Infix,
+                Precedence::MULTIPLICATIVE,
+            ));
+        }
+        if let Some(symbol) = self.prefix {
+            renderers.push(operator(symbol, Notation::Prefix, Precedence::OPPOSITE));
+        }
+        if let Some(symbol) = self.postfix {
+            renderers.push(operator(symbol, Notation::Postfix, Precedence::POSTFIX));
+        }
+        if let Some(template) = self.template {
+            renderers.push(Box::new(TemplateRenderer::new(
+                &template,
+                precedence.unwrap_or(Precedence::ATOM),
+            )?));
+        }
+        if let Some(function) = self.function {
+            renderers.push(Box::new(FunctionRenderer::new(&function)));
+        }
+        if let Some(name) = self.name {
+            renderers.push(Box::new(NameRenderer::new(&name)));
+        }
+        match renderers.len() {
+            1 => Ok(renderers.pop().unwrap()),
+            0 => Err(String::from(
+                "expected one of `infix`, `prefix`, `postfix`, `template`, `function` or `name`",
+            )),
+            _ => Err(String::from("a rule can only have one kind of notation")),
+        }
+    }
+}
+
+/// An error in a rules file
+#[derive(Debug)]
+pub enum RulesError {
+    Io(String),
+    Syntax(String),
+    Invalid { node: String, message: String },
+}
+impl Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Syntax(error) => write!(f, "{}", error),
+            Self::Invalid { node, message } => {
+                write!(f, "invalid rule for `{}`: {}", node, message)
+            }
+        }
+    }
+}
+impl std::error::Error for RulesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::RenderRegistry;
+    use crate::parse_log;
+    use crate::poincare::strip_colors;
+
+    /// short form of the node written in XML as `xml`
+    fn short_form(xml: &str) -> String {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State></Step>"#,
+            xml
+        );
+        let steps = parse_log(&log).unwrap();
+        let node = steps[0].before.as_ref().unwrap();
+        strip_colors(&node.pretty_print(0, false).to_string())
+    }
+
+    fn symbol(name: &str) -> String {
+        format!(r#"<Symbol id="{0}" name="{0}"></Symbol>"#, name)
+    }
+
+    fn integer(value: &str) -> String {
+        format!(r#"<Integer id="{0}" value="{0}"></Integer>"#, value)
+    }
+
+    fn node(name: &str, children: &[String]) -> String {
+        format!(r#"<{0} id="{0}">{1}</{0}>"#, name, children.concat())
+    }
+
+    #[test]
+    fn parenthesizes_looser_operands() {
+        let sum = node("Addition", &[symbol("a"), symbol("b")]);
+        let product = node("Multiplication", &[symbol("a"), symbol("b")]);
+        assert_eq!(
+            short_form(&node("Multiplication", &[sum.clone(), symbol("c")])),
+            "(a + b) * c"
+        );
+        assert_eq!(
+            short_form(&node("Addition", &[product, symbol("c")])),
+            "a * b + c"
+        );
+        assert_eq!(
+            short_form(&node("Power", &[sum.clone(), symbol("c")])),
+            "(a + b) ^ c"
+        );
+        assert_eq!(short_form(&node("Opposite", &[sum])), "-(a + b)");
+    }
+
+    #[test]
+    fn parenthesizes_operands_against_the_associativity() {
+        let difference = node("Subtraction", &[symbol("a"), symbol("b")]);
+        assert_eq!(
+            short_form(&node("Subtraction", &[difference.clone(), symbol("c")])),
+            "a - b - c"
+        );
+        assert_eq!(
+            short_form(&node("Subtraction", &[symbol("c"), difference])),
+            "c - (a - b)"
+        );
+        let power = node("Power", &[symbol("a"), symbol("b")]);
+        assert_eq!(
+            short_form(&node("Power", &[symbol("c"), power.clone()])),
+            "c ^ a ^ b"
+        );
+        assert_eq!(
+            short_form(&node("Power", &[power, symbol("c")])),
+            "(a ^ b) ^ c"
+        );
+    }
+
+    #[test]
+    fn keeps_the_nesting_of_nary_operators() {
+        let sum = node("Addition", &[symbol("a"), symbol("b")]);
+        assert_eq!(
+            short_form(&node("Addition", &[sum.clone(), symbol("c")])),
+            "(a + b) + c"
+        );
+        assert_eq!(
+            short_form(&node("Addition", &[symbol("c"), sum])),
+            "c + (a + b)"
+        );
+    }
+
+    #[test]
+    fn parenthesizes_nested_unary_operators() {
+        let opposite = node("Opposite", &[symbol("x")]);
+        assert_eq!(
+            short_form(&node("Multiplication", &[integer("2"), opposite.clone()])),
+            "2 * (-x)"
+        );
+        assert_eq!(short_form(&node("Opposite", &[opposite])), "-(-x)");
+    }
+
+    #[test]
+    fn gives_numbers_the_precedence_of_their_sign_or_fraction() {
+        let half = r#"<Rational id="r" negative="0" numerator="1" denominator="2"></Rational>"#;
+        let minus_two =
+            r#"<Rational id="m" negative="1" numerator="2" denominator="1"></Rational>"#;
+        assert_eq!(
+            short_form(&node("Power", &[String::from(half), symbol("x")])),
+            "(1/2) ^ x"
+        );
+        assert_eq!(
+            short_form(&node(
+                "Multiplication",
+                &[symbol("x"), String::from(minus_two)]
+            )),
+            "x * (-2/1)"
+        );
+        assert_eq!(
+            short_form(&node("Addition", &[symbol("x"), String::from(half)])),
+            "x + 1/2"
+        );
+    }
+
+    #[test]
+    fn renders_postfix_operators() {
+        assert_eq!(short_form(&node("Factorial", &[symbol("n")])), "n!");
+        assert_eq!(short_form(&node("PercentSimple", &[integer("5")])), "5%");
+        let factorial = node("Factorial", &[symbol("n")]);
+        assert_eq!(short_form(&node("Factorial", &[factorial])), "(n!)!");
+    }
+
+    #[test]
+    fn renders_logical_not() {
+        assert_eq!(
+            short_form(&node("LogicalOperatorNot", &[symbol("p")])),
+            "not p"
+        );
+    }
+
+    #[test]
+    fn renders_comparisons() {
+        let comparison = format!(
+            r#"<Comparison id="c" operator="&lt;=">{}{}</Comparison>"#,
+            symbol("a"),
+            symbol("b")
+        );
+        assert_eq!(short_form(&comparison), "a <= b");
+        let unknown = format!(
+            r#"<Comparison id="c">{}{}</Comparison>"#,
+            symbol("a"),
+            symbol("b")
+        );
+        assert_eq!(short_form(&unknown), "a ? b");
+    }
+
+    #[test]
+    fn renders_equal() {
+        let sum = node("Addition", &[symbol("x"), integer("1")]);
+        assert_eq!(
+            short_form(&node("Equal", &[sum, integer("3")])),
+            "x + 1 = 3"
+        );
+    }
+
+    #[test]
+    fn renders_store() {
+        let function = format!(r#"<Function id="f" name="f">{}</Function>"#, symbol("x"));
+        let sum = node("Addition", &[symbol("x"), integer("1")]);
+        assert_eq!(
+            short_form(&node("Store", &[sum, function])),
+            "x + 1 -> f(x)"
+        );
+    }
+
+    #[test]
+    fn renders_unit_convert() {
+        let unit = |prefix: &str, root_symbol: &str| {
+            format!(
+                r#"<Unit id="{1}" prefix="{0}" rootSymbol="{1}"></Unit>"#,
+                prefix, root_symbol
+            )
+        };
+        let distance = node("Multiplication", &[integer("3"), unit("k", "m")]);
+        assert_eq!(
+            short_form(&node("UnitConvert", &[distance, unit("", "mi")])),
+            "3 * km -> mi"
+        );
+    }
+
+    #[test]
+    fn renders_booleans_and_infinity() {
+        assert_eq!(
+            short_form(r#"<Boolean id="b" value="true"></Boolean>"#),
+            "true"
+        );
+        assert_eq!(
+            short_form(r#"<Infinity id="i" negative="1"></Infinity>"#),
+            "-inf"
+        );
+    }
+
+    #[test]
+    fn renders_matrix_transpose() {
+        assert_eq!(
+            short_form(&node("MatrixTranspose", &[symbol("M")])),
+            "transpose(M)"
+        );
+    }
+
+    #[test]
+    fn renders_sum_and_product() {
+        let square = node("Power", &[symbol("k"), integer("2")]);
+        let bounds = [symbol("k"), integer("1"), symbol("n")];
+        let mut children = vec![square];
+        children.extend(bounds);
+        assert_eq!(short_form(&node("Sum", &children)), "sum(k ^ 2, k, 1, n)");
+        assert_eq!(
+            short_form(&node("Product", &children)),
+            "product(k ^ 2, k, 1, n)"
+        );
+    }
+
+    #[test]
+    fn renders_nth_root() {
+        assert_eq!(
+            short_form(&node("NthRoot", &[symbol("x"), integer("3")])),
+            "root(x, 3)"
+        );
+    }
+
+    #[test]
+    fn renders_functions() {
+        for (name, function) in [
+            ("ArcCosecant", "acsc"),
+            ("Cotangent", "cot"),
+            ("BinomialCoefficient", "binomial"),
+            ("PermuteCoefficient", "permute"),
+            ("ComplexArgument", "arg"),
+            ("DivisionQuotient", "quo"),
+            ("DivisionRemainder", "rem"),
+            ("NormCDF", "normcdf"),
+            ("Determinant", "det"),
+            ("MatrixInverse", "inverse"),
+            ("VectorCross", "cross"),
+            ("Dependency", "dep"),
+            ("ListMean", "mean"),
+            ("ListSum", "sum"),
+            ("PiecewiseOperator", "piecewise"),
+        ] {
+            assert_eq!(
+                short_form(&node(name, &[symbol("a"), symbol("b")])),
+                format!("{}(a, b)", function)
+            );
+        }
+    }
+
+    #[test]
+    fn renders_lists() {
+        let list = node("List", &[integer("1"), integer("2"), integer("3")]);
+        assert_eq!(short_form(&list), "{1, 2, 3}");
+        assert_eq!(short_form(&node("List", &[])), "{}");
+        assert_eq!(
+            short_form(&node("ListElement", &[symbol("L"), integer("2")])),
+            "L(2)"
+        );
+        assert_eq!(
+            short_form(&node(
+                "ListSlice",
+                &[symbol("L"), integer("1"), integer("2")]
+            )),
+            "L(1, 2)"
+        );
+    }
+
+    #[test]
+    fn renders_points_and_percent_additions() {
+        assert_eq!(
+            short_form(&node("Point", &[symbol("x"), symbol("y")])),
+            "(x, y)"
+        );
+        assert_eq!(
+            short_form(&node("PercentAddition", &[integer("50"), integer("10")])),
+            "50 \u{2197} 10%"
+        );
+        assert_eq!(
+            short_form(&node("MixedFraction", &[integer("1"), symbol("q")])),
+            "1 q"
+        );
+    }
+
+    #[test]
+    fn renders_parenthesis_with_braces() {
+        assert_eq!(short_form(&node("Parenthesis", &[symbol("x")])), "{x}");
+    }
+
+    #[test]
+    fn renders_named_nodes() {
+        assert_eq!(short_form(&node("Undefined", &[])), "undef");
+        assert_eq!(short_form(&node("Nonreal", &[])), "nonreal");
+        assert_eq!(short_form(&node("EmptyExpression", &[])), "\u{25a1}");
+        assert_eq!(short_form(&node("Ghost", &[])), "ghost");
+    }
+
+    /// number of children of a node called `name` in Poincare, the nodes displayed with their
+    /// attributes having none
+    fn arity(name: &str) -> usize {
+        match name {
+            "Symbol" | "SymbolAbstract" | "Sequence" | "Function" | "Constant" | "BasedInteger"
+            | "Decimal" | "Float" | "Integer" | "Rational" | "Infinity" | "Boolean" | "Unit"
+            | "CodePointLayout" => 0,
+            "Opposite"
+            | "Factorial"
+            | "PercentSimple"
+            | "LogicalOperatorNot"
+            | "Parenthesis"
+            | "VerticalOffsetLayout"
+            | "NthRootLayout"
+            | "AbsoluteValueLayout"
+            | "CeilingLayout"
+            | "FloorLayout"
+            | "CurlyBraceLayout"
+            | "ParenthesisLayout"
+            | "VectorNormLayout" => 1,
+            "ListSlice" | "CondensedSumLayout" => 3,
+            _ => 2,
+        }
+    }
+
+    #[test]
+    fn renders_every_builtin_node_in_the_short_form() {
+        let registry = RenderRegistry::builtin();
+        // the attributes of all the kinds of nodes, each node only reading its own
+        let attributes = r#"name="x" value="1" base="10" integer="1" CodePoint="x" operator="&lt;"
+            negative="0" mantissa="1" exponent="0" numerator="1" denominator="2" rows="1"
+            columns="2" prefix="" rootSymbol="m" position="Superscript""#;
+        let mut fallbacks = Vec::new();
+        for name in registry.renderers.keys() {
+            let children: Vec<String> = (0..arity(name))
+                .map(|n| symbol(&format!("c{}", n)))
+                .collect();
+            let xml = format!(
+                r#"<{0} id="{0}" {1}>{2}</{0}>"#,
+                name,
+                attributes,
+                children.concat()
+            );
+            let log = format!(
+                r#"<Step name="test"><State name="before">{}</State></Step>"#,
+                xml
+            );
+            let node = parse_log(&log).unwrap().remove(0).before.unwrap();
+            let texts = node.children.iter().map(|child| child.id.clone()).collect();
+            if registry.render(&node, texts).is_none() {
+                fallbacks.push(name.as_str());
+            }
+        }
+        assert!(fallbacks.is_empty(), "long form of {:?}", fallbacks);
+    }
+}
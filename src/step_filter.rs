@@ -0,0 +1,427 @@
+//! Rules hiding the steps which aren't interesting, e.g. the steps doing nothing.
+//!
+//! Each rule is a [`StepFilter`] with a name, so that it can be enabled or disabled from the
+//! command line. [`StepFilters::builtin`] contains the known rules.
+
+use crate::poincare::{CompareMode, PoincareAttributes, PoincareNode};
+use crate::reduction::StepNode;
+
+/// A kind of step which isn't interesting
+pub trait StepFilter: Send + Sync {
+    /// name of the filter, used to enable or disable it
+    fn name(&self) -> &str;
+    /// what the filter hides, in a few words
+    fn description(&self) -> &str;
+    /// true if the filter is used unless it's disabled
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+    /// true if `step` isn't interesting
+    fn hides(&self, step: &StepNode) -> bool;
+}
+
+/// Hides the steps whose result is equal to the expression before them
+pub struct DoesNothing {
+    pub mode: CompareMode,
+}
+impl StepFilter for DoesNothing {
+    fn name(&self) -> &str {
+        "does-nothing"
+    }
+    fn description(&self) -> &str {
+        "steps whose result is equal to the expression before them, see `--compare`"
+    }
+    fn hides(&self, step: &StepNode) -> bool {
+        step.does_nothing(self.mode)
+    }
+}
+
+/// Hides the steps turning a `BasedInteger` into a `Rational` with the same value
+pub struct NumberToRational;
+impl StepFilter for NumberToRational {
+    fn name(&self) -> &str {
+        "number-to-rational"
+    }
+    fn description(&self) -> &str {
+        "steps turning a BasedInteger into a Rational"
+    }
+    fn hides(&self, step: &StepNode) -> bool {
+        match (&step.before, &step.after) {
+            (Some(before), Some(after)) => {
+                step.parts.is_empty() && before.name == "BasedInteger" && after.name == "Rational"
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Hides the steps leading to an `Undefined` node
+pub struct ToUndef;
+impl StepFilter for ToUndef {
+    fn name(&self) -> &str {
+        "to-undef"
+    }
+    fn description(&self) -> &str {
+        "steps whose result contains an Undefined node"
+    }
+    fn hides(&self, step: &StepNode) -> bool {
+        fn node_is_undef(node: &PoincareNode) -> bool {
+            node.name == "Undefined" || node.children.iter().any(node_is_undef)
+        }
+        step.after.as_ref().is_some_and(node_is_undef)
+    }
+}
+
+/// Hides the steps only removing `Parenthesis` nodes
+pub struct ParenthesisRemoval;
+impl StepFilter for ParenthesisRemoval {
+    fn name(&self) -> &str {
+        "parenthesis-removal"
+    }
+    fn description(&self) -> &str {
+        "steps only removing Parenthesis nodes"
+    }
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+    fn hides(&self, step: &StepNode) -> bool {
+        fn without_parentheses(node: &PoincareNode) -> PoincareNode {
+            match node.children.as_slice() {
+                [child] if node.name == "Parenthesis" => without_parentheses(child),
+                children => PoincareNode {
+                    children: children.iter().map(without_parentheses).collect(),
+                    ..node.clone()
+                },
+            }
+        }
+        match (&step.before, &step.after) {
+            (Some(before), Some(after)) => {
+                !before.equals(after, CompareMode::Structural)
+                    && without_parentheses(before)
+                        .equals(&without_parentheses(after), CompareMode::Structural)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Hides the steps only changing how signs are written, e.g. turning `-x` into `-1 * x`, `-(2)`
+/// into `-2` or `a - b` into `a + -b`
+pub struct SignNormalization;
+impl SignNormalization {
+    /// a text representing the node, the same for all the ways of writing a sign
+    fn normal_form(node: &PoincareNode) -> String {
+        fn negate(text: String) -> String {
+            match text
+                .strip_prefix("-(")
+                .and_then(|text| text.strip_suffix(')'))
+            {
+                Some(negated) => String::from(negated),
+                None => format!("-({})", text),
+            }
+        }
+        let unsigned = |sign: &str, rest: String| {
+            if sign == "1" {
+                negate(rest)
+            } else {
+                rest
+            }
+        };
+        let is_minus_one = |node: &PoincareNode| match &node.attributes {
+            Some(PoincareAttributes::Rational {
+                negative,
+                numerator,
+                denominator,
+            }) => negative == "1" && numerator == "1" && denominator == "1",
+            Some(PoincareAttributes::Integer { value }) => value == "-1",
+            _ => false,
+        };
+        match (
+            node.name.as_str(),
+            &node.attributes,
+            node.children.as_slice(),
+        ) {
+            ("Opposite", _, [child]) => negate(Self::normal_form(child)),
+            ("Multiplication", _, [minus_one, child]) if is_minus_one(minus_one) => {
+                negate(Self::normal_form(child))
+            }
+            ("Subtraction", _, [lhs, rhs]) => format!(
+                "Addition[{}, {}]",
+                Self::normal_form(lhs),
+                negate(Self::normal_form(rhs))
+            ),
+            (
+                _,
+                Some(PoincareAttributes::Rational {
+                    negative,
+                    numerator,
+                    denominator,
+                }),
+                _,
+            ) => unsigned(negative, format!("{}/{}", numerator, denominator)),
+            (_, Some(PoincareAttributes::Integer { value }), _) => match value.strip_prefix('-') {
+                Some(value) => negate(String::from(value)),
+                None => value.clone(),
+            },
+            (
+                _,
+                Some(PoincareAttributes::Decimal {
+                    negative,
+                    mantissa,
+                    exponent,
+                }),
+                _,
+            ) => unsigned(negative, format!("{}e{}", mantissa, exponent)),
+            (_, Some(PoincareAttributes::Infinity { negative }), _) => {
+                unsigned(negative, String::from("inf"))
+            }
+            (name, attributes, children) => {
+                let children: Vec<String> = children.iter().map(Self::normal_form).collect();
+                format!(
+                    "{}{}[{}]",
+                    name,
+                    attributes
+                        .as_ref()
+                        .map(|attributes| format!("({})", attributes.pretty_print()))
+                        .unwrap_or_default(),
+                    children.join(", ")
+                )
+            }
+        }
+    }
+}
+impl StepFilter for SignNormalization {
+    fn name(&self) -> &str {
+        "sign-normalization"
+    }
+    fn description(&self) -> &str {
+        "steps only changing how signs are written, e.g. turning -x into -1 * x"
+    }
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+    fn hides(&self, step: &StepNode) -> bool {
+        match (&step.before, &step.after) {
+            (Some(before), Some(after)) => {
+                !before.equals(after, CompareMode::Structural)
+                    && Self::normal_form(before) == Self::normal_form(after)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A set of filters, each enabled or not
+pub struct StepFilters {
+    filters: Vec<(Box<dyn StepFilter>, bool)>,
+}
+impl StepFilters {
+    /// a set without any filter, so that no step is hidden
+    pub fn empty() -> Self {
+        Self {
+            filters: Vec::new(),
+        }
+    }
+    /// the known filters, the steps doing nothing being found according to `compare_mode`
+    pub fn builtin(compare_mode: CompareMode) -> Self {
+        let mut filters = Self::empty();
+        filters.register(DoesNothing { mode: compare_mode });
+        filters.register(NumberToRational);
+        filters.register(ToUndef);
+        filters.register(ParenthesisRemoval);
+        filters.register(SignNormalization);
+        filters
+    }
+    /// adds `filter`, enabled if it's enabled by default
+    pub fn register<F: StepFilter + 'static>(&mut self, filter: F) {
+        let enabled = filter.enabled_by_default();
+        self.filters.push((Box::new(filter), enabled));
+    }
+    /// enables or disables the filter called `name`
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match self
+            .filters
+            .iter_mut()
+            .find(|(filter, _)| filter.name() == name)
+        {
+            Some((_, filter_enabled)) => {
+                *filter_enabled = enabled;
+                Ok(())
+            }
+            None => Err(format!("unknown filter `{}`, see `--list-filters`", name)),
+        }
+    }
+    /// true if an enabled filter hides `step`
+    pub fn hides(&self, step: &StepNode) -> bool {
+        self.filters
+            .iter()
+            .any(|(filter, enabled)| *enabled && filter.hides(step))
+    }
+    /// all the filters, with whether they're enabled
+    pub fn iter(&self) -> impl Iterator<Item = (&dyn StepFilter, bool)> {
+        self.filters
+            .iter()
+            .map(|(filter, enabled)| (filter.as_ref(), *enabled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    fn step(before: &str, after: &str) -> StepNode {
+        let log = format!(
+            r#"<Step name="test"><State name="before">{}</State><State name="after">{}</State></Step>"#,
+            before, after
+        );
+        parse_log(&log).unwrap().remove(0)
+    }
+
+    fn symbol(id: &str, name: &str) -> String {
+        format!(r#"<Symbol id="{}" name="{}"></Symbol>"#, id, name)
+    }
+
+    fn integer(id: &str, value: &str) -> String {
+        format!(r#"<Integer id="{}" value="{}"></Integer>"#, id, value)
+    }
+
+    fn rational(id: &str, negative: &str, numerator: &str, denominator: &str) -> String {
+        format!(
+            r#"<Rational id="{}" negative="{}" numerator="{}" denominator="{}"></Rational>"#,
+            id, negative, numerator, denominator
+        )
+    }
+
+    fn node(name: &str, id: &str, children: &[String]) -> String {
+        format!(r#"<{0} id="{1}">{2}</{0}>"#, name, id, children.concat())
+    }
+
+    /// `-x` and `-1 * x`
+    fn opposite_and_product() -> (String, String) {
+        let x = symbol("1", "x");
+        (
+            node("Opposite", "2", std::slice::from_ref(&x)),
+            node("Multiplication", "3", &[rational("4", "1", "1", "1"), x]),
+        )
+    }
+
+    #[test]
+    fn hides_the_steps_doing_nothing() {
+        let x = symbol("1", "x");
+        let does_nothing =
+            |mode, before: &str, after: &str| DoesNothing { mode }.hides(&step(before, after));
+        assert!(does_nothing(CompareMode::Full, &x, &x));
+        assert!(!does_nothing(CompareMode::Full, &x, &symbol("1", "y")));
+        // the node changed in place
+        assert!(does_nothing(CompareMode::Identity, &x, &symbol("1", "y")));
+        // the node was replaced by a copy
+        assert!(does_nothing(CompareMode::Structural, &x, &symbol("2", "x")));
+    }
+
+    #[test]
+    fn hides_the_numbers_turned_into_rationals() {
+        let two = r#"<BasedInteger id="1" base="10" integer="2"></BasedInteger>"#;
+        assert!(NumberToRational.hides(&step(two, &rational("2", "0", "2", "1"))));
+        assert!(!NumberToRational.hides(&step(two, &integer("2", "2"))));
+    }
+
+    #[test]
+    fn hides_the_steps_leading_to_undefined() {
+        let undefined = node("Undefined", "2", &[]);
+        let sum = node("Addition", "3", &[symbol("1", "x"), undefined.clone()]);
+        assert!(ToUndef.hides(&step(&symbol("1", "x"), &sum)));
+        assert!(ToUndef.hides(&step(&symbol("1", "x"), &undefined)));
+        assert!(!ToUndef.hides(&step(&undefined, &symbol("1", "x"))));
+    }
+
+    #[test]
+    fn hides_the_steps_only_removing_parentheses() {
+        let x = symbol("1", "x");
+        let parenthesized = node(
+            "Addition",
+            "2",
+            &[
+                node("Parenthesis", "3", std::slice::from_ref(&x)),
+                integer("4", "1"),
+            ],
+        );
+        let sum = node("Addition", "2", &[x.clone(), integer("4", "1")]);
+        assert!(ParenthesisRemoval.hides(&step(&parenthesized, &sum)));
+        let other_sum = node("Addition", "2", &[x, integer("4", "2")]);
+        assert!(!ParenthesisRemoval.hides(&step(&parenthesized, &other_sum)));
+        // nothing changed
+        assert!(!ParenthesisRemoval.hides(&step(&sum, &sum)));
+    }
+
+    #[test]
+    fn hides_the_steps_only_changing_how_signs_are_written() {
+        let (opposite, product) = opposite_and_product();
+        assert!(SignNormalization.hides(&step(&opposite, &product)));
+        let opposite_of_two = node("Opposite", "5", &[rational("6", "0", "2", "1")]);
+        assert!(SignNormalization.hides(&step(&opposite_of_two, &rational("7", "1", "2", "1"))));
+        let (a, b) = (symbol("8", "a"), symbol("9", "b"));
+        let difference = node("Subtraction", "10", &[a.clone(), b.clone()]);
+        let sum_of_opposite = node("Addition", "11", &[a, node("Opposite", "12", &[b])]);
+        assert!(SignNormalization.hides(&step(&difference, &sum_of_opposite)));
+    }
+
+    #[test]
+    fn keeps_the_steps_changing_signs_or_values() {
+        let (opposite, _) = opposite_and_product();
+        let x = symbol("1", "x");
+        assert!(!SignNormalization.hides(&step(&opposite, &x)));
+        let times_one = node("Multiplication", "3", &[rational("4", "0", "1", "1"), x]);
+        assert!(!SignNormalization.hides(&step(&opposite, &times_one)));
+        let (a, b) = (symbol("8", "a"), symbol("9", "b"));
+        let difference = node("Subtraction", "10", &[a.clone(), b.clone()]);
+        let sum = node("Addition", "11", &[a, b]);
+        assert!(!SignNormalization.hides(&step(&difference, &sum)));
+        let half = rational("5", "1", "1", "2");
+        assert!(!SignNormalization.hides(&step(&half, &rational("5", "0", "1", "2"))));
+        // a number written as another kind of number isn't a sign change
+        assert!(!SignNormalization.hides(&step(&integer("6", "2"), &rational("6", "0", "2", "1"))));
+    }
+
+    #[test]
+    fn lists_the_builtin_filters() {
+        let filters = StepFilters::builtin(CompareMode::Full);
+        let listed: Vec<(&str, bool)> = filters
+            .iter()
+            .map(|(filter, enabled)| (filter.name(), enabled))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("does-nothing", true),
+                ("number-to-rational", true),
+                ("to-undef", true),
+                ("parenthesis-removal", false),
+                ("sign-normalization", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn enables_the_filters_by_name() {
+        let mut filters = StepFilters::builtin(CompareMode::Full);
+        let (opposite, product) = opposite_and_product();
+        let sign_step = step(&opposite, &product);
+        assert!(!filters.hides(&sign_step));
+        filters.set_enabled("sign-normalization", true).unwrap();
+        assert!(filters.hides(&sign_step));
+        let x = symbol("1", "x");
+        assert!(filters.hides(&step(&x, &x)));
+        filters.set_enabled("does-nothing", false).unwrap();
+        assert!(!filters.hides(&step(&x, &x)));
+    }
+
+    #[test]
+    fn rejects_unknown_filters() {
+        let mut filters = StepFilters::builtin(CompareMode::Full);
+        assert_eq!(
+            filters.set_enabled("sign", true),
+            Err(String::from("unknown filter `sign`, see `--list-filters`"))
+        );
+    }
+}
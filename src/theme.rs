@@ -0,0 +1,405 @@
+//! Colors of the output: whether they're used at all, see [`ColorMode`], and which ones, see
+//! [`Theme`].
+//!
+//! The theme used by [`PoincareNode::pretty_print`], [`crate::TreeDiff::pretty_print`] and the
+//! displays of the steps is the default one, unless another one is installed with
+//! [`Theme::install`].
+//!
+//! [`PoincareNode::pretty_print`]: crate::PoincareNode::pretty_print
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::{self, Display};
+use std::fs::read_to_string;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+use colored::Color;
+use serde::Deserialize;
+
+/// When the output is colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// when the standard output is a terminal, unless the `NO_COLOR` environment variable is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+impl ColorMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "auto" => Self::Auto,
+            "always" => Self::Always,
+            "never" => Self::Never,
+            _ => return None,
+        })
+    }
+    /// whether the output must be colored
+    ///
+    /// See <https://no-color.org> for `NO_COLOR`, which only disables the colors when it isn't
+    /// empty.
+    pub fn enabled(self) -> bool {
+        self.resolve(
+            env::var_os("NO_COLOR").as_deref(),
+            io::stdout().is_terminal(),
+        )
+    }
+    /// whether the output must be colored, given the value of `NO_COLOR` and whether the
+    /// standard output is a terminal
+    fn resolve(self, no_color: Option<&OsStr>, terminal: bool) -> bool {
+        match self {
+            Self::Auto => no_color.is_none_or(|no_color| no_color.is_empty()) && terminal,
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/// Forces the colors on or off until it's dropped, even if a panic unwinds
+///
+/// The override of [`colored`] is global, so it's restored for the messages written meanwhile,
+/// and the colors are only overridden by one thread at a time.
+pub(crate) struct ColorOverride {
+    previous: bool,
+    _lock: MutexGuard<'static, ()>,
+}
+impl ColorOverride {
+    pub(crate) fn set(colors: bool) -> Self {
+        static LOCK: Mutex<()> = Mutex::new(());
+        // the override is restored even when a panic poisons the lock
+        let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = colored::control::SHOULD_COLORIZE.should_colorize();
+        colored::control::set_override(colors);
+        Self {
+            previous,
+            _lock: lock,
+        }
+    }
+}
+impl Drop for ColorOverride {
+    fn drop(&mut self) {
+        colored::control::set_override(self.previous);
+    }
+}
+
+/// The colors of the expressions and of the steps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// colors of the nodes, the nodes inside a node having the next color, and the nodes nested
+    /// in the last color having the first one
+    pub nesting: Vec<Color>,
+    /// color of the nodes inserted by a step, with `--diff`
+    pub inserted: Color,
+    /// color of the nodes removed by a step
+    pub removed: Color,
+    /// color of the nodes moved by a step
+    pub moved: Color,
+    /// color of the nodes whose value was changed by a step
+    pub changed: Color,
+    /// color of the marks and names of the steps, e.g. `/> name`, `|` and `\_`
+    pub step: Color,
+    /// color of the errors and of the messages about the logs, e.g. `log truncated here`
+    pub error: Color,
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            nesting: vec![Color::Yellow, Color::Magenta, Color::Blue],
+            inserted: Color::Green,
+            removed: Color::Red,
+            moved: Color::Cyan,
+            changed: Color::BrightYellow,
+            step: Color::Cyan,
+            error: Color::Red,
+        }
+    }
+}
+impl Theme {
+    /// colors of the Okabe-Ito palette, which can be told apart with any color vision deficiency
+    pub fn colorblind() -> Self {
+        let color = |r, g, b| Color::TrueColor { r, g, b };
+        Self {
+            // orange, sky blue and reddish purple
+            nesting: vec![
+                color(230, 159, 0),
+                color(86, 180, 233),
+                color(204, 121, 167),
+            ],
+            // blue, vermillion, bluish green and yellow
+            inserted: color(0, 114, 178),
+            removed: color(213, 94, 0),
+            moved: color(0, 158, 115),
+            changed: color(240, 228, 66),
+            // bluish green and vermillion, like the default theme where they're cyan and red
+            step: color(0, 158, 115),
+            error: color(213, 94, 0),
+        }
+    }
+    /// the built-in theme called `name`: `default` or `colorblind`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "default" => Self::default(),
+            "colorblind" => Self::colorblind(),
+            _ => return None,
+        })
+    }
+    /// reads a theme from a TOML file, or a JSON file if its extension is `.json`, e.g.
+    /// ```toml
+    /// nesting = ["yellow", "bright magenta", "#3b8eea"]
+    /// removed = "bright red"
+    /// ```
+    ///
+    /// The colors are the names of the 16 colors of the terminal, or RGB colors `#rrggbb`. The
+    /// colors which aren't given are those of the default theme.
+    pub fn load(path: &Path) -> Result<Self, ThemeError> {
+        let content = read_to_string(path).map_err(|e| ThemeError::Io(e.to_string()))?;
+        let file: ThemeFile = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::from_str(&content).map_err(|e| ThemeError::Syntax(e.to_string()))?
+        } else {
+            toml::from_str(&content).map_err(|e| ThemeError::Syntax(e.to_string()))?
+        };
+        let mut theme = Self::default();
+        if let Some(nesting) = file.nesting {
+            if nesting.is_empty() {
+                return Err(ThemeError::NoNestingColor);
+            }
+            theme.nesting = nesting
+                .iter()
+                .map(|name| parse_color(name))
+                .collect::<Result<_, _>>()?;
+        }
+        for (name, color) in [
+            (file.inserted, &mut theme.inserted),
+            (file.removed, &mut theme.removed),
+            (file.moved, &mut theme.moved),
+            (file.changed, &mut theme.changed),
+            (file.step, &mut theme.step),
+            (file.error, &mut theme.error),
+        ] {
+            if let Some(name) = name {
+                *color = parse_color(&name)?;
+            }
+        }
+        Ok(theme)
+    }
+    /// color of the nodes nested in `level` nodes
+    pub fn nesting_color(&self, level: usize) -> Color {
+        self.nesting[level % self.nesting.len()]
+    }
+    /// makes this theme the one used to display the expressions
+    ///
+    /// This can only be done once, before anything is displayed.
+    pub fn install(self) -> Result<(), Self> {
+        THEME.set(self)
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// the theme used to display the expressions
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// A theme file, see [`Theme::load`]
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    nesting: Option<Vec<String>>,
+    inserted: Option<String>,
+    removed: Option<String>,
+    moved: Option<String>,
+    changed: Option<String>,
+    step: Option<String>,
+    error: Option<String>,
+}
+
+/// the color called `name`, e.g. `bright blue`, or given as `#rrggbb`
+fn parse_color(name: &str) -> Result<Color, ThemeError> {
+    let invalid = || ThemeError::InvalidColor(String::from(name));
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let component = |range: std::ops::Range<usize>| {
+            u8::from_str_radix(&hex[range], 16).map_err(|_| invalid())
+        };
+        return Ok(Color::TrueColor {
+            r: component(0..2)?,
+            g: component(2..4)?,
+            b: component(4..6)?,
+        });
+    }
+    Color::from_str(&name.replace('_', " ")).map_err(|_| invalid())
+}
+
+/// An error in a theme file
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(String),
+    Syntax(String),
+    InvalidColor(String),
+    NoNestingColor,
+}
+impl Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Syntax(error) => write!(f, "{}", error),
+            Self::InvalidColor(name) => write!(f, "unknown color `{}`", name),
+            Self::NoNestingColor => write!(f, "`nesting` must have at least one color"),
+        }
+    }
+}
+impl std::error::Error for ThemeError {}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn reads_the_color_modes() {
+        assert_eq!(ColorMode::from_name("auto"), Some(ColorMode::Auto));
+        assert_eq!(ColorMode::from_name("always"), Some(ColorMode::Always));
+        assert_eq!(ColorMode::from_name("never"), Some(ColorMode::Never));
+        assert_eq!(ColorMode::from_name("sometimes"), None);
+    }
+
+    #[test]
+    fn colors_a_terminal_unless_no_color_is_set() {
+        let set = Some(OsStr::new("1"));
+        let empty = Some(OsStr::new(""));
+        assert!(ColorMode::Auto.resolve(None, true));
+        assert!(!ColorMode::Auto.resolve(None, false));
+        assert!(!ColorMode::Auto.resolve(set, true));
+        // an empty `NO_COLOR` doesn't disable the colors
+        assert!(ColorMode::Auto.resolve(empty, true));
+        assert!(ColorMode::Always.resolve(set, false));
+        assert!(!ColorMode::Never.resolve(None, true));
+    }
+
+    #[test]
+    fn doesnt_color_an_output_which_isnt_a_terminal() {
+        // the standard output of the tests is a terminal only when they're run from one without
+        // redirecting it
+        if !io::stdout().is_terminal() {
+            assert!(!ColorMode::Auto.enabled());
+        }
+        assert!(ColorMode::Always.enabled());
+        assert!(!ColorMode::Never.enabled());
+    }
+
+    #[test]
+    fn finds_the_builtin_themes() {
+        assert_eq!(Theme::from_name("default"), Some(Theme::default()));
+        assert_eq!(Theme::from_name("colorblind"), Some(Theme::colorblind()));
+        assert_eq!(Theme::from_name("solarized"), None);
+    }
+
+    #[test]
+    fn tells_the_differences_apart_in_the_colorblind_theme() {
+        let theme = Theme::colorblind();
+        let differences = [theme.inserted, theme.removed, theme.moved, theme.changed];
+        for (n, color) in differences.iter().enumerate() {
+            assert!(!differences[n + 1..].contains(color));
+            assert!(!theme.nesting.contains(color));
+        }
+        assert_ne!(theme.step, theme.error);
+        assert_eq!(
+            theme.nesting_color(4),
+            Color::TrueColor {
+                r: 86,
+                g: 180,
+                b: 233
+            }
+        );
+    }
+
+    /// the theme read from a file called `name`, written with `content`
+    fn load(name: &str, content: &str) -> Result<Theme, ThemeError> {
+        let path = env::temp_dir().join(format!("poincare-theme-{}-{}", process::id(), name));
+        fs::write(&path, content).unwrap();
+        let theme = Theme::load(&path);
+        fs::remove_file(&path).unwrap();
+        theme
+    }
+
+    #[test]
+    fn loads_a_theme_file() {
+        let theme = load(
+            "full.toml",
+            r##"nesting = ["yellow", "bright magenta", "#3b8eea"]
+inserted = "blue"
+removed = "bright_red"
+moved = "white"
+changed = "black"
+step = "#00ff80"
+error = "magenta"
+"##,
+        )
+        .unwrap();
+        assert_eq!(
+            theme,
+            Theme {
+                nesting: vec![
+                    Color::Yellow,
+                    Color::BrightMagenta,
+                    Color::TrueColor {
+                        r: 59,
+                        g: 142,
+                        b: 234
+                    }
+                ],
+                inserted: Color::Blue,
+                removed: Color::BrightRed,
+                moved: Color::White,
+                changed: Color::Black,
+                step: Color::TrueColor {
+                    r: 0,
+                    g: 255,
+                    b: 128
+                },
+                error: Color::Magenta,
+            }
+        );
+        // the colors which aren't given are those of the default theme
+        let theme = load("part.json", r#"{"step": "green"}"#).unwrap();
+        assert_eq!(
+            theme,
+            Theme {
+                step: Color::Green,
+                ..Theme::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reports_the_errors_of_a_theme_file() {
+        let error = |name, content| load(name, content).unwrap_err().to_string();
+        assert_eq!(
+            error("color.toml", r#"error = "crimson""#),
+            "unknown color `crimson`"
+        );
+        assert_eq!(
+            error("hex.toml", r##"step = "#12345""##),
+            "unknown color `#12345`"
+        );
+        assert_eq!(
+            error("nesting.toml", "nesting = []"),
+            "`nesting` must have at least one color"
+        );
+        assert!(error("field.toml", r#"marks = "cyan""#).contains("unknown field `marks`"));
+        assert!(error("syntax.json", "{").contains("EOF"));
+        let missing = Theme::load(Path::new("/nonexistent/theme.toml"));
+        assert!(matches!(missing, Err(ThemeError::Io(_))));
+    }
+}
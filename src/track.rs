@@ -0,0 +1,332 @@
+//! Life story of a node across a log, found with the `id` preserved by Poincare across the steps.
+
+use colored::*;
+
+use crate::diff::TreeDiff;
+use crate::poincare::{CompareMode, PoincareNode};
+use crate::reduction::{StepNode, StepPart};
+use crate::theme::theme;
+
+/// What happens to the tracked node in a state of a step
+#[derive(Debug, Clone)]
+pub enum TrackEvent {
+    /// the node is seen for the first time, or again after it disappeared
+    Appeared,
+    /// the node is the same as the last time it was seen
+    Unchanged,
+    /// the node or its subtree differs from `from`, the last version seen
+    Changed { from: PoincareNode },
+    /// the node has been seen in the step, but isn't in the expression after it
+    Disappeared,
+}
+
+/// A state of a step where the tracked node has been seen
+#[derive(Debug, Clone)]
+pub struct Sighting {
+    /// number of the top-level step containing the state, starting at 1
+    pub top_level_step: usize,
+    /// names of the steps containing the state, from the top-level one
+    pub steps: Vec<String>,
+    /// name of the state, e.g. `before`
+    pub state: String,
+    /// version of the node in the state, or the last version seen if it disappeared
+    pub node: PoincareNode,
+    pub event: TrackEvent,
+}
+impl Sighting {
+    pub fn pretty_print(&self, long_form: bool) -> String {
+        let location = format!(
+            "#{} {} [{}]:",
+            self.top_level_step,
+            self.steps.join(" > "),
+            self.state
+        );
+        let node = self.node.pretty_print(0, long_form);
+        let theme = theme();
+        let event = match &self.event {
+            TrackEvent::Appeared => {
+                format!("{} {}", "appears as".color(theme.inserted).bold(), node)
+            }
+            TrackEvent::Unchanged => format!("{} {}", "is".bold(), node),
+            TrackEvent::Changed { from } => format!(
+                "{} {}",
+                "changes to".color(theme.changed).bold(),
+                TreeDiff::new(from, &self.node).pretty_print(&self.node, long_form)
+            ),
+            TrackEvent::Disappeared => {
+                format!("{} {}", "disappears, was".color(theme.removed).bold(), node)
+            }
+        };
+        format!("{} {}", location.color(theme.step), event)
+    }
+}
+
+/// Follows the node with a given id through the steps of a log, in the order they are read
+pub struct Tracker {
+    id: String,
+    /// last version of the node seen
+    last_seen: Option<PoincareNode>,
+    /// the node hasn't disappeared since it was last seen
+    present: bool,
+    top_level_steps: usize,
+    sightings: usize,
+}
+impl Tracker {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: String::from(id),
+            last_seen: None,
+            present: false,
+            top_level_steps: 0,
+            sightings: 0,
+        }
+    }
+    /// the states of `step` and of its substeps where the node is seen, in the order of the log
+    ///
+    /// The steps must be given in the order of the log, as each sighting is compared to the
+    /// previous one.
+    pub fn track(&mut self, step: &StepNode) -> Vec<Sighting> {
+        self.top_level_steps += 1;
+        let mut sightings = Vec::new();
+        self.track_step(step, &mut Vec::new(), &mut sightings);
+        self.sightings += sightings.len();
+        sightings
+    }
+    /// number of sightings of the node so far
+    pub fn sightings(&self) -> usize {
+        self.sightings
+    }
+    /// looks for the node in `step` and its substeps, returning whether it's seen in any of them
+    fn track_step(
+        &mut self,
+        step: &StepNode,
+        path: &mut Vec<String>,
+        sightings: &mut Vec<Sighting>,
+    ) -> bool {
+        path.push(step.name.clone());
+        // the node is listed once per step when it doesn't change
+        let mut seen_in_step = false;
+        let mut seen = false;
+        if let Some(before) = &step.before {
+            seen |= self.look(before, "before", path, &mut seen_in_step, sightings);
+        }
+        for part in &step.parts {
+            seen |= match part {
+                StepPart::State(name, state) => {
+                    let name = name.as_deref().unwrap_or("state");
+                    self.look(state, name, path, &mut seen_in_step, sightings)
+                }
+                StepPart::Substep(substep) => self.track_step(substep, path, sightings),
+            };
+        }
+        if let Some(after) = &step.after {
+            let in_after = self.look(after, "after", path, &mut seen_in_step, sightings);
+            // the node may have appeared in a substep, and it has already been reported if it
+            // disappeared in a substep
+            if seen && self.present && !in_after {
+                self.present = false;
+                if let Some(last_seen) = &self.last_seen {
+                    sightings.push(self.sighting(
+                        path,
+                        "after",
+                        last_seen.clone(),
+                        TrackEvent::Disappeared,
+                    ));
+                }
+            }
+            seen |= in_after;
+        }
+        path.pop();
+        seen
+    }
+    /// looks for the node in `expression`, returning whether it's there
+    fn look(
+        &mut self,
+        expression: &PoincareNode,
+        state: &str,
+        path: &[String],
+        seen_in_step: &mut bool,
+        sightings: &mut Vec<Sighting>,
+    ) -> bool {
+        let node = match expression.find(&self.id) {
+            Some(node) => node,
+            None => return false,
+        };
+        let event = match &self.last_seen {
+            _ if !self.present => Some(TrackEvent::Appeared),
+            Some(last_seen) if !last_seen.equals(node, CompareMode::Structural) => {
+                Some(TrackEvent::Changed {
+                    from: last_seen.clone(),
+                })
+            }
+            _ if !*seen_in_step => Some(TrackEvent::Unchanged),
+            _ => None,
+        };
+        if let Some(event) = event {
+            sightings.push(self.sighting(path, state, node.clone(), event));
+        }
+        *seen_in_step = true;
+        self.present = true;
+        self.last_seen = Some(node.clone());
+        true
+    }
+    fn sighting(
+        &self,
+        path: &[String],
+        state: &str,
+        node: PoincareNode,
+        event: TrackEvent,
+    ) -> Sighting {
+        Sighting {
+            top_level_step: self.top_level_steps,
+            steps: path.to_vec(),
+            state: String::from(state),
+            node,
+            event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_log;
+
+    fn symbol(id: &str, name: &str) -> String {
+        format!(r#"<Symbol id="{}" name="{}"></Symbol>"#, id, name)
+    }
+
+    fn state(name: &str, node: &str) -> String {
+        format!(r#"<State name="{}">{}</State>"#, name, node)
+    }
+
+    fn step(name: &str, content: &[String]) -> String {
+        format!(r#"<Step name="{}">{}</Step>"#, name, content.concat())
+    }
+
+    /// the events of the sightings of the node `id` in `log`, with the names of their steps
+    fn track(id: &str, log: &str) -> Vec<(String, &'static str)> {
+        let mut tracker = Tracker::new(id);
+        parse_log(log)
+            .unwrap()
+            .iter()
+            .flat_map(|step| tracker.track(step))
+            .map(|sighting| {
+                let event = match sighting.event {
+                    TrackEvent::Appeared => "appeared",
+                    TrackEvent::Unchanged => "unchanged",
+                    TrackEvent::Changed { .. } => "changed",
+                    TrackEvent::Disappeared => "disappeared",
+                };
+                (sighting.steps.join(" > "), event)
+            })
+            .collect()
+    }
+
+    fn events(events: &[(&str, &'static str)]) -> Vec<(String, &'static str)> {
+        events
+            .iter()
+            .map(|(steps, event)| (String::from(*steps), *event))
+            .collect()
+    }
+
+    #[test]
+    fn follows_a_node_through_the_steps() {
+        let log = step(
+            "simplify",
+            &[
+                state("before", &symbol("1", "x")),
+                step(
+                    "rename",
+                    &[
+                        state("before", &symbol("1", "x")),
+                        state("after", &symbol("1", "y")),
+                    ],
+                ),
+                state("after", &symbol("2", "z")),
+            ],
+        );
+        assert_eq!(
+            track("1", &log),
+            events(&[
+                ("simplify", "appeared"),
+                ("simplify > rename", "unchanged"),
+                ("simplify > rename", "changed"),
+                ("simplify", "disappeared"),
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_a_node_appearing_in_a_substep_when_it_disappears() {
+        let log = step(
+            "simplify",
+            &[
+                state("before", &symbol("1", "x")),
+                step(
+                    "create",
+                    &[
+                        state("before", &symbol("1", "x")),
+                        state("after", &symbol("2", "y")),
+                    ],
+                ),
+                state("after", &symbol("3", "z")),
+            ],
+        );
+        assert_eq!(
+            track("2", &log),
+            events(&[
+                ("simplify > create", "appeared"),
+                ("simplify", "disappeared"),
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_a_disappearance_once() {
+        let log = step(
+            "simplify",
+            &[
+                state("before", &symbol("1", "x")),
+                step(
+                    "remove",
+                    &[
+                        state("before", &symbol("1", "x")),
+                        state("after", &symbol("2", "y")),
+                    ],
+                ),
+                state("after", &symbol("2", "y")),
+            ],
+        );
+        assert_eq!(
+            track("1", &log),
+            events(&[
+                ("simplify", "appeared"),
+                ("simplify > remove", "unchanged"),
+                ("simplify > remove", "disappeared"),
+            ])
+        );
+    }
+
+    #[test]
+    fn ignores_the_steps_without_the_node() {
+        let log = [
+            step(
+                "first",
+                &[
+                    state("before", &symbol("1", "x")),
+                    state("after", &symbol("1", "x")),
+                ],
+            ),
+            step(
+                "other",
+                &[
+                    state("before", &symbol("2", "y")),
+                    state("after", &symbol("2", "y")),
+                ],
+            ),
+        ]
+        .concat();
+        assert_eq!(track("1", &log), events(&[("first", "appeared")]));
+    }
+}
@@ -0,0 +1,543 @@
+//! Interactive browser of the steps of a log, displayed in the terminal as a tree whose steps can
+//! be expanded and collapsed.
+//!
+//! The expressions are displayed with [`PoincareNode::pretty_print`], so the rendering rules and
+//! the options of the [`ViewOptions`] apply as when the steps are printed.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::mem;
+use std::panic;
+use std::sync::Arc;
+
+use colored::*;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+
+use crate::diff::TreeDiff;
+use crate::poincare::PoincareNode;
+use crate::reduction::{StepNode, StepPart, ViewOptions};
+use crate::theme::theme;
+
+type PanicHook = dyn Fn(&panic::PanicHookInfo<'_>) + Sync + Send + 'static;
+
+/// The terminal in raw mode on the alternate screen, put back in its normal state when dropped,
+/// e.g. when a panic unwinds
+struct RawTerminal;
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // from here, the raw mode is disabled by the drop
+        let raw_terminal = Self;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(raw_terminal)
+    }
+    /// restores the terminal, returning the first error
+    fn leave(self) -> io::Result<()> {
+        mem::forget(self);
+        Self::restore()
+    }
+    fn restore() -> io::Result<()> {
+        // the raw mode is disabled even if the screen can't be restored
+        let screen = execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let raw_mode = terminal::disable_raw_mode();
+        screen.and(raw_mode)
+    }
+}
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = Self::restore();
+    }
+}
+
+/// number of columns scrolled horizontally at once
+const SHIFT_STEP: usize = 8;
+
+const HELP: &str = "↑↓ move  ←→ collapse/expand  b/a before/after  f long form  / search  \
+                    n/N next/previous  </> scroll  q quit";
+
+/// An expression of a step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum StateKind {
+    Before,
+    After,
+    /// the state at the given index in the parts of the step
+    Part(usize),
+}
+
+/// A line of the tree: a step, or one of its expressions
+///
+/// The path of a step is the index of its top-level step, followed by the indexes of the steps
+/// containing it in the parts of their parent. The path of an expression is the path of its step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    path: Vec<usize>,
+    state: Option<StateKind>,
+}
+
+/// What the keys typed are used for
+enum Mode {
+    Browse,
+    /// the text of a search is being typed
+    Search(String),
+}
+
+/// Browses steps in the terminal, with the expressions displayed according to `options`
+pub struct Browser<'a> {
+    steps: &'a [StepNode],
+    options: &'a ViewOptions,
+    expanded: HashSet<Vec<usize>>,
+    /// the expressions displayed in the other form than the one of the options
+    toggled_form: HashSet<(Vec<usize>, StateKind)>,
+    /// the visible lines, in the order they're displayed
+    rows: Vec<Row>,
+    selected: usize,
+    /// index of the first line displayed
+    scroll: usize,
+    /// number of columns hidden on the left of the lines
+    shift: usize,
+    mode: Mode,
+    last_search: Option<String>,
+    /// displayed in the status line instead of the help until the next key
+    message: Option<String>,
+}
+impl<'a> Browser<'a> {
+    pub fn new(steps: &'a [StepNode], options: &'a ViewOptions) -> Self {
+        let mut browser = Self {
+            steps,
+            options,
+            expanded: HashSet::new(),
+            toggled_form: HashSet::new(),
+            rows: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            shift: 0,
+            mode: Mode::Browse,
+            last_search: None,
+            message: None,
+        };
+        browser.update_rows();
+        browser
+    }
+    /// takes over the terminal until the user quits
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        // the message of a panic would be lost in the alternate screen
+        let previous_hook: Arc<PanicHook> = Arc::from(panic::take_hook());
+        let hook = previous_hook.clone();
+        panic::set_hook(Box::new(move |info| {
+            let _ = RawTerminal::restore();
+            hook(info)
+        }));
+        let result = RawTerminal::enter().and_then(|raw_terminal| {
+            let result = self.event_loop(&mut stdout);
+            // restore the terminal even after an error
+            result.and(raw_terminal.leave())
+        });
+        let _ = panic::take_hook();
+        panic::set_hook(Box::new(move |info| previous_hook(info)));
+        result
+    }
+    fn event_loop(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        loop {
+            self.draw(stdout)?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && !self.handle_key(key) => {
+                    return Ok(());
+                }
+                // the screen is drawn again, e.g. with its new size after a resize
+                _ => (),
+            }
+        }
+    }
+
+    fn step(&self, path: &[usize]) -> &'a StepNode {
+        let mut step = &self.steps[path[0]];
+        for &n in &path[1..] {
+            step = match &step.parts[n] {
+                StepPart::Substep(substep) => substep,
+                StepPart::State(..) => unreachable!("the paths only go through substeps"),
+            };
+        }
+        step
+    }
+    fn expression(&self, path: &[usize], state: StateKind) -> Option<&'a PoincareNode> {
+        let step = self.step(path);
+        match state {
+            StateKind::Before => step.before.as_ref(),
+            StateKind::After => step.after.as_ref(),
+            StateKind::Part(n) => match &step.parts[n] {
+                StepPart::State(_, node) => Some(node),
+                StepPart::Substep(..) => None,
+            },
+        }
+    }
+    /// the paths of all the steps, expanded or not, in the order of the log
+    fn all_paths(&self) -> Vec<Vec<usize>> {
+        fn push_paths(step: &StepNode, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+            paths.push(path.clone());
+            for (n, part) in step.parts.iter().enumerate() {
+                if let StepPart::Substep(substep) = part {
+                    path.push(n);
+                    push_paths(substep, path, paths);
+                    path.pop();
+                }
+            }
+        }
+        let mut paths = Vec::new();
+        for (n, step) in self.steps.iter().enumerate() {
+            push_paths(step, &mut vec![n], &mut paths);
+        }
+        paths
+    }
+
+    fn update_rows(&mut self) {
+        fn push_rows(browser: &Browser, path: &mut Vec<usize>, rows: &mut Vec<Row>) {
+            rows.push(Row {
+                path: path.clone(),
+                state: None,
+            });
+            if !browser.expanded.contains(path) {
+                return;
+            }
+            let step = browser.step(path);
+            let state_row = |path: &[usize], state| Row {
+                path: path.to_vec(),
+                state: Some(state),
+            };
+            if step.before.is_some() {
+                rows.push(state_row(path, StateKind::Before));
+            }
+            for (n, part) in step.parts.iter().enumerate() {
+                match part {
+                    StepPart::State(..) => rows.push(state_row(path, StateKind::Part(n))),
+                    StepPart::Substep(_) => {
+                        path.push(n);
+                        push_rows(browser, path, rows);
+                        path.pop();
+                    }
+                }
+            }
+            if step.after.is_some() {
+                rows.push(state_row(path, StateKind::After));
+            }
+        }
+        let mut rows = Vec::new();
+        for n in 0..self.steps.len() {
+            push_rows(self, &mut vec![n], &mut rows);
+        }
+        self.rows = rows;
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+    /// selects the given line, if it's visible
+    fn select(&mut self, path: &[usize], state: Option<StateKind>) {
+        self.update_rows();
+        if let Some(n) = self
+            .rows
+            .iter()
+            .position(|row| row.path == path && row.state == state)
+        {
+            self.selected = n;
+        }
+    }
+    /// expands the steps containing the step at `path`, so that it's visible
+    fn reveal(&mut self, path: &[usize]) {
+        for length in 1..path.len() {
+            self.expanded.insert(path[..length].to_vec());
+        }
+    }
+
+    fn render_row(&self, row: &Row) -> String {
+        let step = self.step(&row.path);
+        let theme = theme();
+        match row.state {
+            None => {
+                let indent = "  ".repeat(row.path.len() - 1);
+                let mut line = if self.expanded.contains(&row.path) {
+                    format!(
+                        "{}{} {}",
+                        indent,
+                        "▾".color(theme.step).bold(),
+                        step.name.color(theme.step).bold()
+                    )
+                } else {
+                    let marker = format!(
+                        "{}{} {}",
+                        indent,
+                        "▸".color(theme.step).bold(),
+                        step.name.color(theme.step).bold()
+                    );
+                    match step.count_substeps() {
+                        0 => marker,
+                        1 => format!("{} {}", marker, "(1 substep)".color(theme.step)),
+                        count => format!(
+                            "{} {}",
+                            marker,
+                            format!("({} substeps)", count).color(theme.step)
+                        ),
+                    }
+                };
+                if step.incomplete {
+                    line.push_str(&format!(" {}", "log truncated".color(theme.error).bold()));
+                }
+                line
+            }
+            Some(state) => {
+                let indent = "  ".repeat(row.path.len());
+                let label = match state {
+                    StateKind::Before => "before",
+                    StateKind::After => "after",
+                    StateKind::Part(n) => match &step.parts[n] {
+                        StepPart::State(Some(name), _) => name.as_str(),
+                        _ => "state",
+                    },
+                };
+                let long_form = self.options.long_form
+                    != self.toggled_form.contains(&(row.path.clone(), state));
+                let expression = match (state, &step.before, &step.after) {
+                    (StateKind::After, Some(before), Some(after)) if self.options.diff => {
+                        TreeDiff::new(before, after).pretty_print(after, long_form)
+                    }
+                    _ => self
+                        .expression(&row.path, state)
+                        .map(|node| node.pretty_print(0, long_form))
+                        .unwrap_or_default(),
+                };
+                format!(
+                    "{}  {} {}",
+                    indent,
+                    format!("{}:", label).color(theme.step),
+                    expression
+                )
+            }
+        }
+    }
+    fn draw(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        // the last line is the status line
+        let list_height = height.saturating_sub(1).max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+        for y in 0..list_height {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, y as u16),
+                Clear(ClearType::CurrentLine)
+            )?;
+            let n = self.scroll + y;
+            if let Some(row) = self.rows.get(n) {
+                let gutter = if n == self.selected {
+                    "▶ ".bold()
+                } else {
+                    "  ".normal()
+                };
+                let line = clip(&self.render_row(row), self.shift, width.saturating_sub(2));
+                queue!(stdout, Print(gutter), Print(line))?;
+            }
+        }
+        let status = match (&self.mode, &self.message) {
+            (Mode::Search(text), _) => format!("/{}", text),
+            (Mode::Browse, Some(message)) => message.clone(),
+            (Mode::Browse, None) => format!("{}/{}  {}", self.selected + 1, self.rows.len(), HELP),
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(0, list_height as u16),
+            Clear(ClearType::CurrentLine),
+            SetAttribute(Attribute::Reverse),
+            Print(clip(&status, 0, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        stdout.flush()
+    }
+
+    /// handles a key, returning false to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+        if let Mode::Search(text) = &mut self.mode {
+            match key.code {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Enter => {
+                    let text = text.clone();
+                    self.mode = Mode::Browse;
+                    if !text.is_empty() {
+                        self.last_search = Some(text);
+                    }
+                    self.search(true);
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                _ => (),
+            }
+            return true;
+        }
+        self.message = None;
+        let page = terminal::size()
+            .map_or(1, |(_, height)| height.saturating_sub(2) as usize)
+            .max(1);
+        let last = self.rows.len().saturating_sub(1);
+        let row = match self.rows.get(self.selected) {
+            Some(row) => row.clone(),
+            None => return !matches!(key.code, KeyCode::Char('q') | KeyCode::Esc),
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(page),
+            KeyCode::PageDown => self.selected = (self.selected + page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Right | KeyCode::Char('l') if row.state.is_none() => {
+                if self.expanded.insert(row.path.clone()) {
+                    self.update_rows();
+                } else {
+                    // already expanded, go to the first line inside the step
+                    self.selected = (self.selected + 1).min(last);
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                if row.state.is_none() && self.expanded.remove(&row.path) {
+                    self.update_rows();
+                } else {
+                    // go to the step containing the line
+                    let parent = match row.state {
+                        Some(_) => &row.path[..],
+                        None => &row.path[..row.path.len() - 1],
+                    };
+                    if !parent.is_empty() {
+                        self.select(parent, None);
+                    }
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') if row.state.is_none() => {
+                if !self.expanded.remove(&row.path) {
+                    self.expanded.insert(row.path.clone());
+                }
+                self.update_rows();
+            }
+            KeyCode::Char(c @ ('b' | 'a')) => {
+                let (state, name) = if c == 'b' {
+                    (StateKind::Before, "before")
+                } else {
+                    (StateKind::After, "after")
+                };
+                if self.expression(&row.path, state).is_some() {
+                    self.expanded.insert(row.path.clone());
+                    self.select(&row.path, Some(state));
+                } else {
+                    self.message = Some(format!("No expression {} this step", name));
+                }
+            }
+            KeyCode::Char('f') => match row.state {
+                Some(state) => {
+                    let key = (row.path, state);
+                    if !self.toggled_form.remove(&key) {
+                        self.toggled_form.insert(key);
+                    }
+                }
+                None => {
+                    self.message = Some(String::from("Select an expression to change its form"))
+                }
+            },
+            KeyCode::Char('/') => self.mode = Mode::Search(String::new()),
+            KeyCode::Char('n') => self.search(true),
+            KeyCode::Char('N') => self.search(false),
+            KeyCode::Char('<') => self.shift = self.shift.saturating_sub(SHIFT_STEP),
+            KeyCode::Char('>') => self.shift += SHIFT_STEP,
+            _ => (),
+        }
+        true
+    }
+    /// selects the next step, or the previous one if `forward` is false, whose name contains the
+    /// last search, or which contains a node whose id is the last search
+    fn search(&mut self, forward: bool) {
+        let text = match &self.last_search {
+            Some(text) => text.clone(),
+            None => {
+                self.message = Some(String::from("Nothing to search, type / to search"));
+                return;
+            }
+        };
+        let mut paths = self.all_paths();
+        if !forward {
+            paths.reverse();
+        }
+        // start after the step of the selected line, and wrap around
+        let current = self
+            .rows
+            .get(self.selected)
+            .and_then(|row| paths.iter().position(|path| *path == row.path))
+            .unwrap_or(paths.len().saturating_sub(1));
+        let candidates = paths[current + 1..].iter().chain(&paths[..=current]);
+        for path in candidates {
+            let step = self.step(path);
+            let mut states = Vec::new();
+            states.extend(step.before.as_ref().map(|_| StateKind::Before));
+            states.extend(
+                step.parts
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(n, part)| match part {
+                        StepPart::State(..) => Some(StateKind::Part(n)),
+                        StepPart::Substep(..) => None,
+                    }),
+            );
+            states.extend(step.after.as_ref().map(|_| StateKind::After));
+            let state_with_node = states.into_iter().find(|&state| {
+                self.expression(path, state)
+                    .is_some_and(|node| node.find(&text).is_some())
+            });
+            if let Some(state) = state_with_node {
+                let path = path.clone();
+                self.reveal(&path);
+                self.expanded.insert(path.clone());
+                self.select(&path, Some(state));
+                return;
+            }
+            if step.name.contains(&text) {
+                let path = path.clone();
+                self.reveal(&path);
+                self.select(&path, None);
+                return;
+            }
+        }
+        self.message = Some(format!("No step named `{}` nor node with this id", text));
+    }
+}
+
+/// the part of `text` displayed when its first `skip` columns are hidden and only `width` columns
+/// fit, keeping the ANSI escape codes so that the colors are right
+fn clip(text: &str, skip: usize, width: usize) -> String {
+    let mut clipped = String::new();
+    let mut column = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            clipped.push(c);
+            for c in chars.by_ref() {
+                clipped.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if column >= skip && column < skip + width {
+            clipped.push(c);
+        }
+        column += 1;
+    }
+    clipped.push_str("\x1b[0m");
+    clipped
+}